@@ -1,32 +1,83 @@
 //! API and Rocket-related types
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::net::IpAddr;
 use std::ops::Deref;
 
 use log::error;
 use rocket::fairing::{Fairing, Info, Kind};
-use rocket::http::{ContentType, RawStr, Status, StatusClass};
-use rocket::request::FromParam;
+use rocket::http::{ContentType, Header, RawStr, Status, StatusClass};
+use rocket::request::{self, FromParam, FromRequest};
 use rocket::response::{status, Responder, Result as ResponseResult};
-use rocket::{Request, Response};
+use rocket::{Outcome, Request, Response, State};
 use rocket_contrib::json::Json;
 use serde::de::{Deserializer, Error as _, Unexpected};
+use serde::ser::{SerializeMap, Serializer};
 use serde::{Deserialize, Serialize};
 use std::io::Cursor;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::services::{is_timeout, unavailable_cause};
 
 /// JSON payload sent by the server on HTTP errors
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ErrorPayload {
+    /// Reason phrase of the status code.
     pub error: String,
+    /// HTTP status code.
+    pub code: u16,
+    /// Machine-readable failure type such as `not_found` or `upstream_timeout`, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    /// Every invalid item of the request, for requests rejected by validation.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub invalid: Vec<InvalidEntry>,
+    /// Similar names of existing pokemon, for pokemon which weren't found.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<String>,
+}
+
+/// Invalid item of a request body, see `ErrorPayload::invalid`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InvalidEntry {
+    /// Position of the item in the request.
+    pub index: usize,
+    pub value: String,
+    pub reason: String,
 }
 
 impl ErrorPayload {
-    pub fn new<S: Into<String>>(s: S) -> Self {
-        ErrorPayload { error: s.into() }
+    /// Replaces the failure type derived from the status code.
+    pub fn with_kind<S: Into<String>>(mut self, kind: S) -> Self {
+        self.kind = Some(kind.into());
+        self
     }
 }
 
 impl From<Status> for ErrorPayload {
     fn from(status: Status) -> Self {
-        ErrorPayload::new(status.reason)
+        let kind = match status.code {
+            400 => Some("bad_request"),
+            401 => Some("unauthorized"),
+            404 => Some("not_found"),
+            416 => Some("range_not_satisfiable"),
+            429 => Some("rate_limited"),
+            500 => Some("internal"),
+            503 => Some("unavailable"),
+            504 => Some("upstream_timeout"),
+            _ => None,
+        };
+        ErrorPayload {
+            error: status.reason.into(),
+            code: status.code,
+            kind: kind.map(String::from),
+            invalid: Vec::new(),
+            suggestions: Vec::new(),
+        }
     }
 }
 
@@ -65,6 +116,15 @@ pub type Result<T> = std::result::Result<Json<T>, Error>;
 #[derive(Debug)]
 pub enum Error {
     Status(Status),
+    /// 404 with the given `kind` in the payload, telling why there is no description.
+    NotFound(&'static str),
+    /// 400 listing every invalid item of the request.
+    Invalid(Vec<InvalidEntry>),
+    /// 404 with the given `kind`, suggesting similar names of existing pokemon.
+    NotFoundSuggesting(&'static str, Vec<String>),
+    /// The client exceeded its rate limit, responds with 429 and `Retry-After` set to the given
+    /// delay.
+    RateLimited(Duration),
     Other(anyhow::Error),
 }
 
@@ -79,18 +139,526 @@ where
 
 impl<'r> Responder<'r> for Error {
     fn respond_to(self, request: &Request) -> ResponseResult<'r> {
+        let mut retry_after = None;
+        let mut kind = None;
+        let mut invalid = Vec::new();
+        let mut suggestions = Vec::new();
         let status = match self {
             Error::Status(s) => s,
+            Error::NotFound(reason) => {
+                kind = Some(reason);
+                Status::NotFound
+            }
+            Error::NotFoundSuggesting(reason, names) => {
+                kind = Some(reason);
+                suggestions = names;
+                Status::NotFound
+            }
+            Error::Invalid(entries) => {
+                invalid = entries;
+                Status::BadRequest
+            }
+            Error::RateLimited(delay) => {
+                retry_after = Some(delay);
+                Status::TooManyRequests
+            }
             Error::Other(e) => {
                 error!("{}", e);
-                Status::InternalServerError
+                if is_timeout(&e) {
+                    Status::GatewayTimeout
+                } else if let Some(unavailable) = unavailable_cause(&e) {
+                    retry_after = Some(unavailable.retry_after());
+                    kind = Some(if unavailable.is_rate_limited() {
+                        "rate_limited"
+                    } else {
+                        "upstream_unavailable"
+                    });
+                    Status::ServiceUnavailable
+                } else {
+                    Status::InternalServerError
+                }
             }
         };
-        status::Custom(status, Json(ErrorPayload::new(status.reason))).respond_to(request)
+        let mut payload = ErrorPayload::from(status);
+        if let Some(kind) = kind {
+            payload = payload.with_kind(kind);
+        }
+        payload.invalid = invalid;
+        payload.suggestions = suggestions;
+        let mut response = status::Custom(status, Json(payload)).respond_to(request)?;
+        if let Some(delay) = retry_after {
+            response.set_raw_header("Retry-After", retry_after_secs(delay).to_string());
+        }
+        Ok(response)
+    }
+}
+
+/// Whole seconds for a `Retry-After` header, rounded up.
+fn retry_after_secs(delay: Duration) -> u64 {
+    delay.as_secs() + u64::from(delay.subsec_nanos() > 0)
+}
+
+/// Responder which serves the byte range requested with a `Range` header out of successful
+/// responses, if enabled. Only single `bytes` ranges are supported, other `Range` headers are
+/// ignored and the full body is served.
+#[derive(Debug)]
+pub struct Ranged<R> {
+    inner: R,
+    enabled: bool,
+}
+
+impl<R> Ranged<R> {
+    pub fn new(inner: R, enabled: bool) -> Self {
+        Ranged { inner, enabled }
+    }
+}
+
+impl<'r, R: Responder<'r>> Responder<'r> for Ranged<R> {
+    fn respond_to(self, request: &Request) -> ResponseResult<'r> {
+        let mut response = self.inner.respond_to(request)?;
+        if !self.enabled || response.status() != Status::Ok {
+            return Ok(response);
+        }
+        response.set_raw_header("Accept-Ranges", "bytes");
+        let range = match request.headers().get_one("Range") {
+            Some(range) => range,
+            None => return Ok(response),
+        };
+        let body = response.body_bytes().unwrap_or_default();
+        let len = body.len();
+        match byte_range(range, len) {
+            None => response.set_sized_body(Cursor::new(body)),
+            Some(Some((start, end))) => {
+                response.set_status(Status::PartialContent);
+                response
+                    .set_raw_header("Content-Range", format!("bytes {}-{}/{}", start, end, len));
+                response.set_sized_body(Cursor::new(body[start..=end].to_vec()));
+            }
+            Some(None) => {
+                let status = Status::RangeNotSatisfiable;
+                response.set_status(status);
+                response.set_raw_header("Content-Range", format!("bytes */{}", len));
+                // The error replaces the representation, whatever format or tag it had
+                response.set_header(ContentType::JSON);
+                response.remove_header("ETag");
+                let body = serde_json::to_vec(&ErrorPayload::from(status))
+                    .expect("ErrorPayload should be serializable");
+                response.set_sized_body(Cursor::new(body));
+            }
+        }
+        Ok(response)
+    }
+}
+
+/// Responder which sets an `ETag` hashed from the body of successful responses together with a
+/// `variant` identifying how the response was produced, such as its cache key. Responds with 304
+/// Not Modified and no body instead if the tag matches the request's `If-None-Match`.
+#[derive(Debug)]
+pub struct Tagged<R> {
+    inner: R,
+    variant: u64,
+}
+
+impl<R> Tagged<R> {
+    pub fn new<V: Hash>(inner: R, variant: &V) -> Self {
+        let mut hasher = DefaultHasher::new();
+        variant.hash(&mut hasher);
+        Tagged {
+            inner,
+            variant: hasher.finish(),
+        }
+    }
+}
+
+impl<'r, R: Responder<'r>> Responder<'r> for Tagged<R> {
+    fn respond_to(self, request: &Request) -> ResponseResult<'r> {
+        let mut response = self.inner.respond_to(request)?;
+        if response.status() != Status::Ok {
+            return Ok(response);
+        }
+        let body = response.body_bytes().unwrap_or_default();
+        let mut hasher = DefaultHasher::new();
+        hasher.write_u64(self.variant);
+        hasher.write(&body);
+        let etag = format!("\"{:016x}\"", hasher.finish());
+        let not_modified = request
+            .headers()
+            .get("If-None-Match")
+            .any(|header| etag_matches(header, &etag));
+        if not_modified {
+            response.set_status(Status::NotModified);
+            response.take_body();
+            response.remove_header("Content-Type");
+        } else {
+            response.set_sized_body(Cursor::new(body));
+        }
+        response.set_raw_header("ETag", etag);
+        Ok(response)
+    }
+}
+
+/// Whether an `If-None-Match` header matches `etag`, using weak comparison.
+fn etag_matches(header: &str, etag: &str) -> bool {
+    header
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
+
+/// Parses a `Range` header for a body of `len` bytes into inclusive bounds. Returns `None` if the
+/// header isn't a single `bytes` range and should be ignored, `Some(None)` if the range can't be
+/// satisfied.
+fn byte_range(header: &str, len: usize) -> Option<Option<(usize, usize)>> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let mut bounds = spec.splitn(2, '-');
+    let start = bounds.next()?.trim();
+    let end = bounds.next()?.trim();
+    let range = match (start, end) {
+        ("", "") => return None,
+        ("", suffix) => {
+            let suffix: usize = suffix.parse().ok()?;
+            if suffix == 0 || len == 0 {
+                None
+            } else {
+                Some((len.saturating_sub(suffix), len - 1))
+            }
+        }
+        (start, end) => {
+            let start: usize = start.parse().ok()?;
+            let end = match end {
+                "" => len.saturating_sub(1),
+                end => end.parse::<usize>().ok()?.min(len.saturating_sub(1)),
+            };
+            if start >= len || start > end {
+                None
+            } else {
+                Some((start, end))
+            }
+        }
+    };
+    Some(range)
+}
+
+/// Responder which serializes the value as MessagePack if enabled and the client accepts
+/// `application/msgpack`, and as JSON otherwise.
+#[derive(Debug)]
+pub struct Negotiated<T> {
+    value: T,
+    msgpack: bool,
+}
+
+impl<T> Negotiated<T> {
+    pub fn new(value: T, msgpack: bool) -> Self {
+        Negotiated { value, msgpack }
+    }
+}
+
+impl<'r, T: Serialize> Responder<'r> for Negotiated<T> {
+    fn respond_to(self, request: &Request) -> ResponseResult<'r> {
+        let accepts_msgpack = request.accept().map_or(false, |accept| {
+            accept
+                .media_types()
+                .any(|m| m.top() == "application" && m.sub() == "msgpack")
+        });
+        if !self.msgpack {
+            return Json(self.value).respond_to(request);
+        }
+        // The body depends on Accept, so caches mustn't serve one representation for the other
+        let vary = Header::new("Vary", "Accept");
+        if !accepts_msgpack {
+            return Response::build_from(Json(self.value).respond_to(request)?)
+                .header(vary)
+                .ok();
+        }
+        match rmp_serde::to_vec_named(&self.value) {
+            Ok(body) => Response::build()
+                .header(ContentType::new("application", "msgpack"))
+                .header(vary)
+                .sized_body(Cursor::new(body))
+                .ok(),
+            Err(e) => {
+                error!("Failed to serialize response as MessagePack: {}", e);
+                Err(Status::InternalServerError)
+            }
+        }
+    }
+}
+
+/// Serializes the value nested under `key` if specified, or as is otherwise.
+#[derive(Debug)]
+pub struct Rooted<T> {
+    key: Option<String>,
+    value: T,
+}
+
+impl<T> Rooted<T> {
+    pub fn new(value: T, key: Option<String>) -> Self {
+        Rooted { key, value }
+    }
+}
+
+impl<T: Serialize> Serialize for Rooted<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self.key {
+            Some(ref key) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(key, &self.value)?;
+                map.end()
+            }
+            None => self.value.serialize(serializer),
+        }
+    }
+}
+
+/// Caps how many requests are handled at once, see `InflightPermit`.
+#[derive(Debug)]
+pub struct InflightLimiter {
+    max: Option<usize>,
+    current: AtomicUsize,
+}
+
+impl InflightLimiter {
+    /// Creates a limiter which never refuses requests.
+    pub fn unlimited() -> Self {
+        InflightLimiter {
+            max: None,
+            current: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn new(max: usize) -> Self {
+        InflightLimiter {
+            max: Some(max),
+            current: AtomicUsize::new(0),
+        }
+    }
+
+    /// Takes a slot, returns `None` if all slots are taken.
+    pub fn try_acquire(&self) -> Option<InflightPermit> {
+        let prev = self.current.fetch_add(1, Ordering::SeqCst);
+        match self.max {
+            Some(max) if prev >= max => {
+                self.current.fetch_sub(1, Ordering::SeqCst);
+                None
+            }
+            _ => Some(InflightPermit(self)),
+        }
+    }
+}
+
+/// Request guard holding a slot of the managed `InflightLimiter` until the request is handled.
+/// Fails with 503 if all slots are taken.
+#[derive(Debug)]
+pub struct InflightPermit<'r>(&'r InflightLimiter);
+
+impl<'r> Drop for InflightPermit<'r> {
+    fn drop(&mut self) {
+        self.0.current.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for InflightPermit<'r> {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, ()> {
+        let limiter = request.guard::<State<'r, InflightLimiter>>()?.inner();
+        match limiter.try_acquire() {
+            Some(permit) => Outcome::Success(permit),
+            None => Outcome::Failure((Status::ServiceUnavailable, ())),
+        }
+    }
+}
+
+/// Client a rate limit applies to: a known API key, or the client IP for unknown or absent keys.
+/// As a request guard, identifies the client like `WithinRateLimit` without counting the request.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum RateLimitClient {
+    Key(String),
+    Ip(Option<IpAddr>),
+}
+
+/// Caps how many requests each client makes per hour, see `WithinRateLimit`.
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_hour: Option<u32>,
+    keys: Vec<String>,
+    /// Start of the current window and the requests made in it for each client.
+    windows: Mutex<HashMap<RateLimitClient, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    const WINDOW: Duration = Duration::from_secs(60 * 60);
+
+    /// Creates a limiter which never refuses requests.
+    pub fn unlimited() -> Self {
+        RateLimiter {
+            per_hour: None,
+            keys: Vec::new(),
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Creates a limiter allowing `per_hour` requests to each of the API `keys`, and as many to
+    /// each client IP for requests without a known key.
+    pub fn new(per_hour: u32, keys: Vec<String>) -> Self {
+        RateLimiter {
+            per_hour: Some(per_hour),
+            keys,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Counts `n` requests from `client`, failing with the time until the client may make requests
+    /// again if they exceed its limit.
+    pub fn acquire_n(
+        &self,
+        client: RateLimitClient,
+        n: u32,
+    ) -> std::result::Result<(), RateLimited> {
+        self.acquire(client, n, Instant::now()).map_err(RateLimited)
+    }
+
+    /// Counts `n` requests from `client`, returns how long until they're allowed if they exceed the
+    /// limit. Nothing is counted in that case.
+    fn acquire(
+        &self,
+        client: RateLimitClient,
+        n: u32,
+        now: Instant,
+    ) -> std::result::Result<(), Duration> {
+        let per_hour = match self.per_hour {
+            Some(per_hour) => per_hour,
+            None => return Ok(()),
+        };
+        let mut windows = self.windows.lock().unwrap();
+        if !windows.contains_key(&client) {
+            windows.retain(|_, (start, _)| now.saturating_duration_since(*start) < Self::WINDOW);
+        }
+        let (start, count) = windows.entry(client).or_insert((now, 0));
+        if now.saturating_duration_since(*start) >= Self::WINDOW {
+            *start = now;
+            *count = 0;
+        }
+        if count.saturating_add(n) > per_hour {
+            return Err(Self::WINDOW - now.saturating_duration_since(*start));
+        }
+        *count += n;
+        Ok(())
     }
 }
 
-/// String containing only alphabetic characters.
+/// Rejection of `WithinRateLimit`, with the time until the client may make requests again.
+#[derive(Debug)]
+pub struct RateLimited(pub Duration);
+
+/// Request guard counting the request against the managed `RateLimiter`. Clients are identified
+/// by an `Authorization: Bearer <key>` header with one of the configured API keys, or by the IP
+/// they connect from otherwise. `X-Real-IP` is ignored since clients can set it freely. Take it as `Result<WithinRateLimit, RateLimited>` to respond with
+/// `Error::RateLimited`.
+#[derive(Debug)]
+pub struct WithinRateLimit;
+
+impl<'a, 'r> FromRequest<'a, 'r> for WithinRateLimit {
+    type Error = RateLimited;
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, RateLimited> {
+        // Without a managed limiter, fail like `State` does
+        let failed = |(status, ())| (status, RateLimited(Duration::from_secs(0)));
+        let limiter = request.guard::<State<RateLimiter>>().map_failure(failed)?;
+        let client = request.guard::<RateLimitClient>().map_failure(failed)?;
+        match limiter.acquire(client, 1, Instant::now()) {
+            Ok(()) => Outcome::Success(WithinRateLimit),
+            Err(delay) => Outcome::Failure((Status::TooManyRequests, RateLimited(delay))),
+        }
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for RateLimitClient {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, ()> {
+        let limiter = request.guard::<State<RateLimiter>>()?;
+        let key = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .filter(|key| limiter.keys.iter().any(|k| k == key));
+        Outcome::Success(match key {
+            Some(key) => RateLimitClient::Key(key.to_string()),
+            None => RateLimitClient::Ip(request.remote().map(|addr| addr.ip())),
+        })
+    }
+}
+
+/// Limits on request headers enforced by `HeadersWithinLimits`, `None` disables a limit.
+#[derive(Clone, Debug, Default)]
+pub struct HeaderLimits {
+    /// Max size of a single header, name and value, in bytes.
+    pub max_header_bytes: Option<usize>,
+    /// Max number of headers.
+    pub max_headers: Option<usize>,
+}
+
+/// Request guard which fails with 400 if the request headers exceed the managed `HeaderLimits`.
+#[derive(Debug)]
+pub struct HeadersWithinLimits;
+
+impl<'a, 'r> FromRequest<'a, 'r> for HeadersWithinLimits {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, ()> {
+        let limits = request.guard::<State<HeaderLimits>>()?;
+        let headers = request.headers();
+        let too_many = limits.max_headers.map_or(false, |max| headers.len() > max);
+        let too_large = limits.max_header_bytes.map_or(false, |max| {
+            headers
+                .iter()
+                .any(|h| h.name().len() + h.value().len() > max)
+        });
+        if too_many || too_large {
+            Outcome::Failure((Status::BadRequest, ()))
+        } else {
+            Outcome::Success(HeadersWithinLimits)
+        }
+    }
+}
+
+/// Bearer token required by admin endpoints, `None` disables them. See `Admin`.
+#[derive(Clone, Debug)]
+pub struct AdminToken(pub Option<String>);
+
+/// Request guard for admin endpoints. Requires an `Authorization: Bearer <token>` header matching
+/// the managed `AdminToken`, fails with 401 otherwise, or with 404 if admin endpoints are
+/// disabled.
+#[derive(Debug)]
+pub struct Admin;
+
+impl<'a, 'r> FromRequest<'a, 'r> for Admin {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, ()> {
+        let token = match request.guard::<State<AdminToken>>()?.inner().0 {
+            Some(ref token) => token,
+            None => return Outcome::Failure((Status::NotFound, ())),
+        };
+        let authorized = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .map_or(false, |t| t == token);
+        if authorized {
+            Outcome::Success(Admin)
+        } else {
+            Outcome::Failure((Status::Unauthorized, ()))
+        }
+    }
+}
+
+/// Pokemon name: a string of alphabetic characters, `♀`/`♂` symbols and hyphens, which can't
+/// start or end with a hyphen.
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
 pub struct Alpha(String);
 
@@ -100,14 +668,104 @@ impl Alpha {
     /// This type implements `FromParam` and `Deserialize`, so it can be used for validation in
     /// rocket and serde, respectively.
     pub fn try_new(s: String) -> Option<Self> {
-        if !s.is_empty() && s.chars().all(char::is_alphabetic) {
-            Some(Alpha(s))
+        Alpha::parse(s).ok()
+    }
+
+    /// Like `try_new`, but returns why the input is invalid, see `validate`.
+    pub fn parse(s: String) -> std::result::Result<Self, &'static str> {
+        Alpha::validate(&s).map(|()| Alpha(s))
+    }
+
+    /// Checks whether `s` is a valid name, returns why it isn't otherwise.
+    pub fn validate(s: &str) -> std::result::Result<(), &'static str> {
+        if s.is_empty() {
+            Err("name is empty")
+        } else if s.starts_with('-') || s.ends_with('-') {
+            Err("name starts or ends with a hyphen")
+        } else if !s
+            .chars()
+            .all(|c| c.is_alphabetic() || c == '-' || c == '♀' || c == '♂')
+        {
+            Err("name contains characters other than letters, hyphens, ♀ and ♂")
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Length of the longest run of a single repeated character.
+    pub fn longest_run(&self) -> usize {
+        let mut longest = 0;
+        let mut current = 0;
+        let mut prev = None;
+        for c in self.0.chars() {
+            if prev == Some(c) {
+                current += 1;
+            } else {
+                current = 1;
+                prev = Some(c);
+            }
+            longest = longest.max(current);
+        }
+        longest
+    }
+
+    /// Canonical form of the name, as used by PokeAPI. Gender symbols are spelled out, e.g.
+    /// `Nidoran♀` becomes `nidoran-f`.
+    pub fn canonical(&self) -> Alpha {
+        let name = self.0.to_lowercase().replace('♀', "-f").replace('♂', "-m");
+        Alpha(name)
+    }
+}
+
+/// Pokemon species, referenced either by name or by Pokédex number. Formats as the identifier
+/// PokeAPI expects.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub enum Species {
+    Name(Alpha),
+    Id(u32),
+}
+
+impl Species {
+    /// Parses a Pokédex number, returns `None` unless `s` only contains digits.
+    pub fn parse_id(s: &str) -> Option<Self> {
+        if !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()) {
+            s.parse().ok().map(Species::Id)
         } else {
             None
         }
     }
 }
 
+impl Serialize for Species {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Species {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Species, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer).and_then(|s| {
+            Species::parse_id(&s)
+                .or_else(|| Alpha::try_new(s.clone()).map(Species::Name))
+                .ok_or_else(|| {
+                    D::Error::invalid_value(Unexpected::Str(&s), &"a pokemon name or number")
+                })
+        })
+    }
+}
+
+impl fmt::Display for Species {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Species::Name(name) => f.write_str(name),
+            Species::Id(id) => write!(f, "{}", id),
+        }
+    }
+}
+
 impl Into<String> for Alpha {
     fn into(self) -> String {
         self.0
@@ -130,16 +788,20 @@ impl<'r> FromParam<'r> for Alpha {
     }
 }
 
+impl Serialize for Alpha {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self)
+    }
+}
+
 impl<'de> Deserialize<'de> for Alpha {
     fn deserialize<D>(deserializer: D) -> std::result::Result<Alpha, D::Error>
     where
         D: Deserializer<'de>,
     {
         String::deserialize(deserializer).and_then(|s| {
-            Alpha::try_new(s.clone()).ok_or(D::Error::invalid_value(
-                Unexpected::Str(&s),
-                &"an alpha string",
-            ))
+            Alpha::try_new(s.clone())
+                .ok_or_else(|| D::Error::invalid_value(Unexpected::Str(&s), &"an alpha string"))
         })
     }
 }
@@ -152,11 +814,69 @@ mod test {
     use rocket::local::Client;
     use rocket::{get, routes};
 
+    #[test]
+    fn test_rate_limiter() {
+        let now = Instant::now();
+        let limiter = RateLimiter::new(2, vec!["alice".into()]);
+        let alice = || RateLimitClient::Key("alice".into());
+        let ip = || RateLimitClient::Ip(None);
+
+        assert_eq!(limiter.acquire(alice(), 1, now), Ok(()));
+        assert_eq!(limiter.acquire(alice(), 1, now), Ok(()));
+        let later = now + Duration::from_secs(60);
+        assert_eq!(
+            limiter.acquire(alice(), 1, later),
+            Err(Duration::from_secs(59 * 60))
+        );
+        assert_eq!(limiter.acquire(ip(), 1, later), Ok(()));
+        assert_eq!(
+            limiter.acquire(alice(), 1, now + RateLimiter::WINDOW),
+            Ok(())
+        );
+
+        // Requests exceeding the remaining limit aren't counted
+        assert!(limiter.acquire(ip(), 2, later).is_err());
+        assert_eq!(limiter.acquire(ip(), 1, later), Ok(()));
+
+        let unlimited = RateLimiter::unlimited();
+        for _ in 0..10 {
+            assert_eq!(unlimited.acquire(ip(), 5, now), Ok(()));
+        }
+    }
+
     #[test]
     fn test_alpha_parse_ok() {
         Alpha::from_param("foo".into()).unwrap();
     }
 
+    #[test]
+    fn test_alpha_canonical() {
+        let name = Alpha::from_param("Pikachu".into()).unwrap();
+        assert_eq!(&*name.canonical(), "pikachu");
+        let name = Alpha::try_new("Nidoran♀".into()).unwrap();
+        assert_eq!(&*name.canonical(), "nidoran-f");
+    }
+
+    #[test]
+    fn test_alpha_hyphens_and_symbols() {
+        for name in &["mr-mime", "ho-oh", "type-null", "nidoran-f", "Nidoran♂"] {
+            Alpha::try_new(name.to_string()).unwrap();
+        }
+        for name in &["-mime", "mime-", "-", "mr mime", "mr/mime", "foo&20bar"] {
+            assert_eq!(Alpha::try_new(name.to_string()), None);
+        }
+    }
+
+    #[test]
+    fn test_species_parse_id() {
+        assert_eq!(Species::parse_id("25"), Some(Species::Id(25)));
+        assert_eq!(Species::parse_id("025").unwrap().to_string(), "25");
+        assert_eq!(Species::parse_id("+25"), None);
+        assert_eq!(Species::parse_id("pikachu"), None);
+        assert_eq!(Species::parse_id(""), None);
+        assert_eq!(Species::parse_id("99999999999"), None);
+    }
+
     #[test]
     fn test_alpha_parse_invalid() {
         Alpha::from_param("".into()).unwrap_err();
@@ -164,6 +884,39 @@ mod test {
         Alpha::from_param(".".into()).unwrap_err();
     }
 
+    #[test]
+    fn test_alpha_validate() {
+        assert_eq!(Alpha::validate("ho-oh"), Ok(()));
+        assert_eq!(Alpha::validate(""), Err("name is empty"));
+        assert_eq!(
+            Alpha::validate("mime-"),
+            Err("name starts or ends with a hyphen")
+        );
+        Alpha::validate("p1kachu").unwrap_err();
+    }
+
+    #[test]
+    fn test_etag_matches() {
+        assert!(etag_matches(r#""abc""#, r#""abc""#));
+        assert!(etag_matches(r#""x", W/"abc""#, r#""abc""#));
+        assert!(etag_matches("*", r#""abc""#));
+        assert!(!etag_matches(r#""abcd""#, r#""abc""#));
+        assert!(!etag_matches("abc", r#""abc""#));
+    }
+
+    #[test]
+    fn test_byte_range() {
+        assert_eq!(byte_range("bytes=0-9", 20), Some(Some((0, 9))));
+        assert_eq!(byte_range("bytes=10-", 20), Some(Some((10, 19))));
+        assert_eq!(byte_range("bytes=-5", 20), Some(Some((15, 19))));
+        assert_eq!(byte_range("bytes=5-100", 20), Some(Some((5, 19))));
+        assert_eq!(byte_range("bytes=20-30", 20), Some(None));
+        assert_eq!(byte_range("bytes=9-0", 20), Some(None));
+        assert_eq!(byte_range("bytes=0-1,5-6", 20), None);
+        assert_eq!(byte_range("items=0-9", 20), None);
+        assert_eq!(byte_range("bytes=a-b", 20), None);
+    }
+
     #[test]
     fn test_serialize_errors() {
         #[get("/status?<code>")]
@@ -177,16 +930,22 @@ mod test {
                 .map_or(Error::Other(anyhow!("Some error")), Error::Status)
         }
 
+        #[get("/limited")]
+        fn limited() -> Error {
+            Error::RateLimited(Duration::from_secs(1))
+        }
+
         let rocket = rocket::ignite()
             .attach(SerializeErrors)
-            .mount("/", routes![status, err]);
+            .mount("/", routes![status, err, limited]);
         let client = Client::new(rocket).unwrap();
-        for (status, endpoint) in vec![
+        for &(status, endpoint) in &[
             (Status::NotFound, "/foo"),
             (Status::BadRequest, "/status?code=400"),
             (Status::InternalServerError, "/status?code=500"),
             (Status::InternalServerError, "/err"),
             (Status::BadRequest, "/err?code=400"),
+            (Status::TooManyRequests, "/limited"),
         ] {
             let mut response = client.get(endpoint).dispatch();
             assert_eq!(response.content_type(), Some(ContentType::JSON));
@@ -195,5 +954,17 @@ mod test {
             let payload = serde_json::from_slice(&bytes).unwrap();
             assert_eq!(ErrorPayload::from(status), payload);
         }
+
+        let mut response = client.get("/foo").dispatch();
+        let body: serde_json::Value =
+            serde_json::from_str(&response.body_string().unwrap()).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({"error": "Not Found", "code": 404, "kind": "not_found"})
+        );
+        let mut response = client.get("/limited").dispatch();
+        let payload: ErrorPayload = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+        assert_eq!(payload.code, 429);
+        assert_eq!(payload.kind.as_deref(), Some("rate_limited"));
     }
 }
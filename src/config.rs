@@ -1,32 +1,85 @@
 //! This module handles application-specific configuration in the Rocket.toml file. See
-//! `ReadConfig`.
+//! `ReadConfig` and `ReadOptions`.
 use std::collections::HashMap;
+use std::env;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
 
-use log::error;
-use rocket::config::ConfigError;
+use anyhow::{bail, Context};
+use log::{error, info, warn};
+use reqwest::Url;
+use rocket::config::{Config, ConfigError};
 use rocket::fairing::{Fairing, Info, Kind};
 use rocket::Rocket;
-use serde::de::Deserializer;
-use serde::Deserialize;
+use serde::de::{DeserializeOwned, Deserializer, Error as _, Unexpected};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 
-use crate::api::Alpha;
-use crate::services::{BoxedPokeApi, BoxedTranslator, Cache, FunTranslationsApi, PokeApiClient};
+use crate::api::{AdminToken, Alpha, HeaderLimits, InflightLimiter, RateLimiter};
+use crate::services::{
+    BoxedPokeApi, BoxedTranslator, Cache, CanonicalNames, ClientOptions, DeadLetterLog, Eviction,
+    FlavorTextStrategy, FunTranslationsApi, KnownNames, LoadShedder, Lookup, PokeApiClient, Style,
+    DEFAULT_USER_AGENT,
+};
 
 /// Fairing which parses extra configuration on launch and instantiates the necessary services. The
 /// following config keys are defined:
 ///
 /// * cache_size(integer): Max translations to keep cached, defaults to 4096.
+/// * cache_ttl_secs(integer): If specified, cached translations expire after this many seconds and
+/// are fetched again. Cached translations never expire by default.
+/// * request_timeout_secs(integer): If specified, requests to PokeAPI and Fun Translations fail
+/// after this many seconds, answering `/pokemon` with 504. Defaults to reqwest's 30 seconds.
+/// * user_agent(string): `User-Agent` header of requests to PokeAPI and Fun Translations, defaults
+/// to `poke_shakespeare/<version>`.
+/// * cache_path(string): If specified, cached translations are loaded from this JSON file on launch
+/// and saved to it periodically, so they survive restarts. Missing or unreadable files start with
+/// an empty cache, and so do files saved with a different PokeAPI or Fun Translations
+/// configuration, `empty_description` or `funtranslations` options shaping the descriptions.
+/// * cache.flush_interval_secs(integer): How often cached translations are saved to `cache_path`
+/// if they changed, in seconds. Changes made since the last save are lost when the application
+/// stops. Defaults to 60.
+/// * cache.eviction(string): Policy used to evict translations when the cache is full, either
+/// `lru` (least recently used) or `lfu` (least frequently used). Defaults to `lru`.
+/// * cache.validate_on_read(boolean): If true, cached translations which are empty or contain form
+/// feeds are fetched and translated again instead of being served. Defaults to false.
+/// * cache.audit_log_size(integer): If specified, the admin endpoint `GET /cache/log` lists up to
+/// this many recent cache insertions, evictions and removals with their timestamps and keys.
 /// * pokeapi.mock(table): Mapping of pokemon names to descriptions. If specified, the application
 /// references this table instead of fetching descriptions from PokeAPI.
 /// * pokeapi.url(string): Pokemon species endpoint, defaults to
-/// https://pokeapi.co/api/v2/pokemon-species/.
+/// https://pokeapi.co/api/v2/pokemon-species/. Must be an http or https URL, http logs a warning.
+/// * pokeapi.path_template(string): Path appended to `pokeapi.url` to fetch a species, `{name}` is
+/// replaced by the pokemon name. Defaults to `{name}`.
+/// * pokeapi.flavor_text_strategy(string): Which flavor text of a species is used as its
+/// description: `first`, `last`, `longest` or `random`. Defaults to `first`.
+/// * pokeapi.language(string): Language of the descriptions, english ones are used for pokemon
+/// without flavor text in this language. Defaults to `en`.
 /// * funtranslations.mock(boolean): if true, the application will do mock translations instead of
 /// accessing the Fun Translations API.
+/// * funtranslations.mock_format(string): Format of mock translations, `{text}` is replaced by the
+/// source description and `{chars}` by its length in characters. Defaults to
+/// `MOCKED TRANSLATION: {text}`.
 /// * funtranslations.url(string): Shakespeare translation endpoint, defaults to
-/// https://api.funtranslations.com/translate/shakespeare/.
+/// https://api.funtranslations.com/translate/shakespeare/. Must be an http or https URL, http
+/// logs a warning.
+/// * funtranslations.max_attempts(integer): Attempts made while Fun Translations responds with
+/// 429 or 503, answering `/pokemon` with 503 once exhausted. Between 1 and 10, defaults to 3.
+/// * funtranslations.retry_base_delay_ms(integer): Delay before the first retry in milliseconds,
+/// doubled on each further retry unless the API sends `Retry-After`. Defaults to 500.
+/// * funtranslations.max_retry_delay_ms(integer): Longest delay before a retry in milliseconds.
+/// Requests fail right away if the API asks to wait longer with `Retry-After`. Defaults to 10000.
 /// * funtranslations.api_key(string): Secret to authenticate the Fun Translations API with. If
 /// unspecified, API calls will be unauthenticated. Note that unauthenticated calls are
 /// rate-limited.
+/// * funtranslations.require_reachable(boolean): If true, launching fails when Fun Translations
+/// can't be reached, unless Fun Translations is mocked or `funtranslations.fallback_to_source` is set.
+/// Defaults to false.
+///
+/// The `POKEAPI_URL`, `FUNTRANSLATIONS_URL` and `FUNTRANSLATIONS_API_KEY` environment variables
+/// take precedence over `pokeapi.url`, `funtranslations.url` and `funtranslations.api_key`
+/// respectively. They're ignored for mocked services.
 pub struct ReadConfig;
 
 impl Fairing for ReadConfig {
@@ -45,64 +98,825 @@ impl Fairing for ReadConfig {
             error!("Invalid cache size {}", cache_size);
             return Err(rocket);
         }
-        let cache: Cache = Cache::new(cache_size as usize);
+        let cache_ttl_secs = match cfg.get_int("cache_ttl_secs") {
+            Ok(ttl) if ttl > 0 => Some(ttl as u64),
+            Ok(ttl) => {
+                error!("Invalid cache TTL {}", ttl);
+                return Err(rocket);
+            }
+            Err(ConfigError::Missing(_)) => None,
+            Err(e) => {
+                error!("Configuration error: {}", e);
+                return Err(rocket);
+            }
+        };
+        let request_timeout_secs = match cfg.get_int("request_timeout_secs") {
+            Ok(secs) if secs > 0 => Some(secs as u64),
+            Ok(secs) => {
+                error!("Invalid request timeout {}", secs);
+                return Err(rocket);
+            }
+            Err(ConfigError::Missing(_)) => None,
+            Err(e) => {
+                error!("Configuration error: {}", e);
+                return Err(rocket);
+            }
+        };
+        let user_agent = match cfg.get_string("user_agent") {
+            Ok(user_agent) => user_agent,
+            Err(ConfigError::Missing(_)) => DEFAULT_USER_AGENT.into(),
+            Err(e) => {
+                error!("Configuration error: {}", e);
+                return Err(rocket);
+            }
+        };
+        let client_options = ClientOptions {
+            user_agent,
+            timeout: request_timeout_secs.map(Duration::from_secs),
+        };
+
+        #[derive(Default, Deserialize)]
+        struct CacheOptions {
+            #[serde(default)]
+            eviction: Eviction,
+            #[serde(default)]
+            validate_on_read: bool,
+            #[serde(default)]
+            audit_log_size: Option<usize>,
+            #[serde(default)]
+            flush_interval_secs: Option<u64>,
+        }
 
-        let pokeapi = match cfg.get_extra("pokeapi").and_then(|v| {
-            v.clone().try_into::<PokeApiConfig>().map_err(|e| {
-                ConfigError::ParseError(
-                    "".into(),
-                    "Rocket.toml".into(),
-                    e.to_string(),
-                    e.line_col(),
-                )
-            })
-        }) {
-            Ok(cfg) => cfg.into_client(),
-            Err(ConfigError::Missing(_)) => Box::new(PokeApiClient::default()),
+        let cache_options = match get_table::<CacheOptions>(cfg, "cache") {
+            Ok(opts) => opts.unwrap_or_default(),
+            Err(e) => {
+                error!("Configuration error: {}", e);
+                return Err(rocket);
+            }
+        };
+        let mut cache = Cache::with_eviction(
+            cache_size as usize,
+            cache_options.eviction,
+            cache_ttl_secs.map(Duration::from_secs),
+        )
+        .validate_on_read(cache_options.validate_on_read);
+        match cache_options.audit_log_size {
+            Some(0) => {
+                error!("Invalid cache audit log size 0");
+                return Err(rocket);
+            }
+            Some(size) => cache = cache.with_audit_log(size),
+            None => (),
+        }
+        let cache_path = match cfg.get_string("cache_path") {
+            Ok(path) => Some(PathBuf::from(path)),
+            Err(ConfigError::Missing(_)) => None,
             Err(e) => {
                 error!("Configuration error: {}", e);
                 return Err(rocket);
             }
         };
 
-        let translator = match cfg.get_extra("funtranslations").and_then(|v| {
-            v.clone().try_into::<TranslatorConfig>().map_err(|e| {
-                ConfigError::ParseError(
-                    "".into(),
-                    "Rocket.toml".into(),
-                    e.to_string(),
-                    e.line_col(),
-                )
-            })
-        }) {
-            Ok(cfg) => cfg.into_translator(),
-            Err(ConfigError::Missing(_)) => Box::new(FunTranslationsApi::default()),
+        let pokeapi_config = match get_table::<PokeApiConfig>(cfg, "pokeapi") {
+            Ok(cfg) => cfg.unwrap_or_default(),
+            Err(e) => {
+                error!("Configuration error: {}", e);
+                return Err(rocket);
+            }
+        };
+        let pokeapi_config = match pokeapi_config.with_env_overrides(|k| env::var(k).ok()) {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                error!("Configuration error: {:#}", e);
+                return Err(rocket);
+            }
+        };
+
+        let translator_config = match get_table::<TranslatorConfig>(cfg, "funtranslations") {
+            Ok(cfg) => cfg.unwrap_or_default(),
             Err(e) => {
                 error!("Configuration error: {}", e);
                 return Err(rocket);
             }
         };
+        let translator_config = match translator_config.with_env_overrides(|k| env::var(k).ok()) {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                error!("Configuration error: {:#}", e);
+                return Err(rocket);
+            }
+        };
+
+        if let Some(ref path) = cache_path {
+            let fingerprint = match cache_fingerprint(cfg, &pokeapi_config, &translator_config) {
+                Ok(fingerprint) => fingerprint,
+                Err(e) => {
+                    error!("Configuration error: {}", e);
+                    return Err(rocket);
+                }
+            };
+            cache = cache.with_fingerprint(fingerprint);
+            match cache.load(path) {
+                Ok(n) => info!("Loaded {} cached translations from {}", n, path.display()),
+                Err(e) => warn!("Starting with an empty cache: {:#}", e),
+            }
+            let flush_interval_secs = match cache_options.flush_interval_secs {
+                Some(0) => {
+                    error!("Invalid cache flush interval 0");
+                    return Err(rocket);
+                }
+                Some(secs) => secs,
+                None => 60,
+            };
+            cache = cache.persist_to(path.clone(), Duration::from_secs(flush_interval_secs));
+        }
+
+        #[derive(Default, Deserialize)]
+        struct StartupOptions {
+            #[serde(default)]
+            require_reachable: bool,
+            #[serde(default)]
+            fallback_to_source: bool,
+        }
+
+        let startup_options = match get_table::<StartupOptions>(cfg, "funtranslations") {
+            Ok(opts) => opts.unwrap_or_default(),
+            Err(e) => {
+                error!("Configuration error: {}", e);
+                return Err(rocket);
+            }
+        };
+        let check_reachable = startup_options.require_reachable
+            && !startup_options.fallback_to_source
+            && matches!(translator_config, TranslatorConfig::Concrete { .. });
+
+        let services_config = ServicesConfig {
+            cache_size: cache_size as usize,
+            cache_ttl_secs,
+            cache_eviction: cache_options.eviction,
+            cache_validate_on_read: cache_options.validate_on_read,
+            cache_audit_log_size: cache_options.audit_log_size,
+            cache_path,
+            cache_flush_interval_secs: cache_options.flush_interval_secs,
+            request_timeout_secs,
+            user_agent: client_options.user_agent.clone(),
+            pokeapi: pokeapi_config.describe(),
+            funtranslations: translator_config.describe(),
+        };
+        let pokeapi = pokeapi_config.into_client(&client_options);
+        let translator = translator_config.into_translator(&client_options);
+        if check_reachable {
+            if let Err(e) = translator.ping() {
+                error!(
+                    "Fun Translations is unreachable and funtranslations.fallback_to_source isn't \
+                     set: {:#}",
+                    e
+                );
+                return Err(rocket);
+            }
+        }
+
+        Ok(rocket
+            .manage(cache)
+            .manage(pokeapi)
+            .manage(translator)
+            .manage(services_config))
+    }
+}
+
+/// Identifies the configuration cached translations depend on, so a persisted cache is discarded
+/// when the translator, PokeAPI or the options shaping descriptions change.
+fn cache_fingerprint(
+    cfg: &Config,
+    pokeapi_config: &PokeApiConfig,
+    translator_config: &TranslatorConfig,
+) -> Result<String, ConfigError> {
+    const TEXT_OPTIONS: &[&str] = &[
+        "style",
+        "clean_output",
+        "title_case",
+        "fix_yoda_output",
+        "skip_if_archaic",
+        "min_chars_to_translate",
+    ];
+
+    let funtranslations =
+        get_table::<serde_json::Map<String, serde_json::Value>>(cfg, "funtranslations")?
+            .unwrap_or_default();
+    let text_options: serde_json::Map<_, _> = funtranslations
+        .into_iter()
+        .filter(|(k, _)| TEXT_OPTIONS.contains(&k.as_str()))
+        .collect();
+    let empty_description = match cfg.get_string("empty_description") {
+        Ok(s) => Some(s),
+        Err(ConfigError::Missing(_)) => None,
+        Err(e) => return Err(e),
+    };
+    Ok(json!({
+        "pokeapi": pokeapi_config.describe(),
+        "funtranslations": translator_config.describe(),
+        "text_options": text_options,
+        "empty_description": empty_description,
+    })
+    .to_string())
+}
+
+/// Service configuration read by `ReadConfig`, with secrets redacted. Served by `/config`.
+#[derive(Clone, Debug, Serialize)]
+pub struct ServicesConfig {
+    pub cache_size: usize,
+    pub cache_ttl_secs: Option<u64>,
+    pub cache_eviction: Eviction,
+    pub cache_validate_on_read: bool,
+    pub cache_audit_log_size: Option<usize>,
+    pub cache_path: Option<PathBuf>,
+    pub cache_flush_interval_secs: Option<u64>,
+    pub request_timeout_secs: Option<u64>,
+    pub user_agent: String,
+    pub pokeapi: serde_json::Value,
+    pub funtranslations: serde_json::Value,
+}
+
+/// Fairing which parses configuration that applies regardless of how services were instantiated,
+/// so it's attached by both `RocketExt` methods. The following config keys are defined:
+///
+/// * include_timestamp(boolean): If true, responses include a `generated_at` RFC3339 timestamp of
+/// when the description was calculated. Defaults to false.
+/// * include_names(boolean): If true, responses include the `requested_name` as sent by the client
+/// and the `canonical_name` it was resolved to. Defaults to false.
+/// * empty_description(string): Description returned as-is when PokeAPI's description is empty or
+/// only whitespace. If unspecified, such pokemon are treated as having no description. Either way
+/// the translator isn't called.
+/// * include_reading_time(boolean): If true, responses include `reading_time_seconds`, an
+/// estimate of how long the description takes to read. Defaults to false.
+/// * reading_wpm(integer): Reading speed in words per minute used for `reading_time_seconds`.
+/// Defaults to 200.
+/// * include_source_url(boolean): If true, responses include the `source_url` of the PokeAPI
+/// resource the description was fetched from. Defaults to false.
+/// * strip_apostrophes(boolean): If true, apostrophes are removed from requested names before
+/// validation, so `Farfetch'd` resolves to `farfetchd`. Defaults to false.
+/// * normalize_forms(boolean): If true, form descriptions such as `Mega Charizard X` or
+/// `Gigantamax Pikachu` are resolved to PokeAPI slugs like `charizard-mega-x` and `pikachu-gmax`.
+/// Defaults to false.
+/// * aliases(table): Mapping of alternate spellings to the pokemon names they resolve to.
+/// * include_aliases(boolean): If true, responses include the `aliases` resolving to the pokemon.
+/// Defaults to false.
+/// * canonical_cache_size(integer): Max raw names to keep mapped to their canonical form. Defaults
+/// to 0, which disables the mapping.
+/// * max_repeated_chars(integer): If specified, names containing a run of the same character
+/// longer than this are rejected with 400.
+/// * chunk_size(integer): Size in characters of the description chunks returned for `?chunk=`,
+/// unless the request specifies `chunk_size`. Defaults to 280.
+/// * cache_unknown_names(boolean): If false, lookups of pokemon which weren't found are not
+/// cached, unless the name is listed in `known_names_file`. Defaults to true.
+/// * known_names_file(string): Path to a file listing known species names, one per line. Names not
+/// in the list are answered with 404 without querying PokeAPI. If unspecified or unreadable, all
+/// names are looked up.
+/// * debug.enabled(boolean): If true, `/pokemon` honors `?debug_translation=true` by including the
+/// raw translation service response under `debug`, `POST /translate` translates arbitrary text,
+/// `GET /debug/cache/order` lists cached keys from the most to the least recently used and the
+/// admin endpoint `POST /debug/fail-health` toggles `/healthz` into failing. Never enable this in
+/// production. Defaults to false.
+/// * admin.token(string): Bearer token required by admin endpoints such as `/config`. Admin
+/// endpoints respond with 404 if unspecified.
+/// * funtranslations.clean_output(boolean): If true, runs of whitespace in translations are
+/// collapsed into single spaces. Defaults to false.
+/// * funtranslations.title_case(boolean): If true, translations are converted to English title
+/// case, e.g. for display as headings. Minor words such as "the" or "of" are lowercased unless
+/// they start or end the text. Defaults to false.
+/// * funtranslations.fix_yoda_output(boolean): If true, Yoda translations are capitalized and end
+/// in punctuation, which Fun Translations' Yoda output often lacks. Defaults to false.
+/// * pokeapi.static_descriptions(table): Mapping of pokemon names to pre-written descriptions,
+/// served untranslated with `source: "static"` when fetching or translating the description fails.
+/// * funtranslations.dead_letter_file(string): Path to a file where failed translations are
+/// appended as JSON lines with the pokemon name, source text and error. Disabled if unspecified.
+/// * funtranslations.style(string): Default translation style, `shakespeare`, `yoda` or `pirate`,
+/// which requests can override with `?style=`. Other styles are translated with the endpoints
+/// next to `funtranslations.url`. Defaults to `shakespeare`.
+/// * funtranslations.fallback_to_source(boolean): If true, the untranslated description is served
+/// with `translated: false` when translating it fails, instead of an error. Such descriptions
+/// aren't cached. Defaults to false.
+/// * funtranslations.skip_if_archaic(boolean): If true, source descriptions which already contain
+/// archaic words such as "thee" or "hath" are returned untranslated, with `source: "raw_archaic"`
+/// in the response. Defaults to false.
+/// * funtranslations.min_chars_to_translate(integer): Source descriptions with fewer characters
+/// than this are returned untranslated, with `source: "raw_short"` in the response, to save
+/// translation quota. Defaults to 0, translating every description.
+/// * loadshed.miss_rate_threshold(float): If specified, requests which miss the cache are answered
+/// with 503 while the rate of cache misses is above this fraction, only cache hits are served.
+/// * loadshed.window_secs(integer): Window over which the miss rate is computed, defaults to 60.
+/// * loadshed.min_requests(integer): Minimum requests in the window before shedding kicks in,
+/// defaults to 100.
+/// * ratelimit.per_key_per_hour(integer): If specified, `/pokemon` requests beyond this many per
+/// hour from a single client are answered with 429 and a `Retry-After` header. Clients are
+/// identified by an `Authorization: Bearer <key>` header with one of `ratelimit.keys`, or by the
+/// IP they connect from if they send no known key. `POST /pokemon` counts each distinct name as a
+/// request.
+/// * batch.allow_empty(boolean): If true, `POST /pokemon` answers an empty list of names with an
+/// empty list, otherwise with 400. Defaults to false.
+/// * ratelimit.keys(array of strings): Client API keys which are rate-limited independently of the
+/// client IP. Defaults to none.
+/// * http.max_inflight_requests(integer): If specified, `/pokemon` requests beyond this many being
+/// handled at once are answered with 503.
+/// * http.max_header_bytes(integer): If specified, `/pokemon` requests with a header, name and
+/// value, larger than this many bytes are rejected with 400.
+/// * http.max_headers(integer): If specified, `/pokemon` requests with more headers than this are
+/// rejected with 400.
+/// * http.msgpack(boolean): If true, `/pokemon` responses are serialized as MessagePack for
+/// clients sending `Accept: application/msgpack`. Defaults to false.
+/// * metrics.enabled(boolean): If true, `/metrics` exposes request and cache metrics in the
+/// Prometheus text format, otherwise it responds with 404. Defaults to false.
+/// * metrics.latency_percentiles(boolean): If true, the latency of every request is tracked and
+/// `/stats/latency` lists the p50, p95 and p99 latencies of each endpoint as JSON, otherwise it
+/// responds with 404. Defaults to false.
+/// * http.options_method(boolean): If true, `OPTIONS /pokemon/<name>` answers with 204 and an
+/// `Allow` header listing the supported methods. Defaults to false.
+/// * http.root_key(string): If specified, successful `/pokemon` responses are nested under this
+/// key, e.g. `{"pokemon": {...}}`. Errors are left as is.
+/// * http.range_requests(boolean): If true, `/pokemon` honors single `Range: bytes=` requests on
+/// the response body with 206 Partial Content. Defaults to false.
+pub struct ReadOptions;
+
+/// Settings for `/pokemon` responses, see `ReadOptions`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Options {
+    pub include_timestamp: bool,
+    pub include_names: bool,
+    pub include_reading_time: bool,
+    pub reading_wpm: u64,
+    pub include_source_url: bool,
+    pub strip_apostrophes: bool,
+    pub normalize_forms: bool,
+    pub aliases: HashMap<Alpha, Alpha>,
+    pub include_aliases: bool,
+    pub cache_unknown_names: bool,
+    pub empty_description: Option<String>,
+    pub max_repeated_chars: Option<usize>,
+    pub debug: bool,
+    pub chunk_size: usize,
+    pub skip_if_archaic: bool,
+    pub min_chars_to_translate: usize,
+    pub fallback_to_source: bool,
+    pub style: Style,
+    pub clean_output: bool,
+    pub title_case: bool,
+    pub fix_yoda_output: bool,
+    pub range_requests: bool,
+    pub msgpack: bool,
+    pub options_method: bool,
+    pub root_key: Option<String>,
+    pub metrics: bool,
+    pub latency_percentiles: bool,
+    pub static_descriptions: HashMap<String, String>,
+    pub batch_allow_empty: bool,
+}
+
+/// Options used when none are configured.
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            include_timestamp: false,
+            include_names: false,
+            include_reading_time: false,
+            reading_wpm: 200,
+            include_source_url: false,
+            strip_apostrophes: false,
+            normalize_forms: false,
+            aliases: HashMap::new(),
+            include_aliases: false,
+            cache_unknown_names: true,
+            empty_description: None,
+            max_repeated_chars: None,
+            debug: false,
+            chunk_size: 280,
+            skip_if_archaic: false,
+            min_chars_to_translate: 0,
+            fallback_to_source: false,
+            style: Style::default(),
+            clean_output: false,
+            title_case: false,
+            fix_yoda_output: false,
+            range_requests: false,
+            msgpack: false,
+            options_method: false,
+            root_key: None,
+            metrics: false,
+            latency_percentiles: false,
+            static_descriptions: HashMap::new(),
+            batch_allow_empty: false,
+        }
+    }
+}
+
+impl Fairing for ReadOptions {
+    fn info(&self) -> Info {
+        Info {
+            name: "Read Options",
+            kind: Kind::Attach,
+        }
+    }
+
+    fn on_attach(&self, rocket: Rocket) -> Result<Rocket, Rocket> {
+        match read_options(rocket.config()) {
+            Ok(state) => Ok(rocket
+                .manage(state.options)
+                .manage(state.canonical_names)
+                .manage(state.dead_letters)
+                .manage(state.load_shedder)
+                .manage(state.inflight_limiter)
+                .manage(state.rate_limiter)
+                .manage(state.header_limits)
+                .manage(state.admin_token)
+                .manage(state.known_names)),
+            Err(e) => {
+                error!("Configuration error: {:#}", e);
+                Err(rocket)
+            }
+        }
+    }
+}
+
+/// State managed by `ReadOptions`.
+struct OptionsState {
+    options: Options,
+    canonical_names: CanonicalNames,
+    dead_letters: DeadLetterLog,
+    load_shedder: LoadShedder,
+    inflight_limiter: InflightLimiter,
+    rate_limiter: RateLimiter,
+    header_limits: HeaderLimits,
+    admin_token: AdminToken,
+    known_names: KnownNames,
+}
+
+fn read_options(cfg: &Config) -> anyhow::Result<OptionsState> {
+    #[derive(Deserialize)]
+    struct HttpOptions {
+        #[serde(default)]
+        max_inflight_requests: Option<usize>,
+        #[serde(default)]
+        range_requests: bool,
+        #[serde(default)]
+        msgpack: bool,
+        #[serde(default)]
+        options_method: bool,
+        #[serde(default)]
+        root_key: Option<String>,
+        #[serde(default)]
+        max_header_bytes: Option<usize>,
+        #[serde(default)]
+        max_headers: Option<usize>,
+    }
+
+    #[derive(Deserialize)]
+    struct AdminOptions {
+        #[serde(default)]
+        token: Option<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct DebugOptions {
+        #[serde(default)]
+        enabled: bool,
+    }
+
+    #[derive(Deserialize)]
+    struct MetricsOptions {
+        #[serde(default)]
+        enabled: bool,
+        #[serde(default)]
+        latency_percentiles: bool,
+    }
+
+    #[derive(Deserialize)]
+    struct PokeApiOptions {
+        #[serde(default)]
+        static_descriptions: HashMap<Alpha, String>,
+    }
+
+    #[derive(Deserialize)]
+    struct TranslatorOptions {
+        #[serde(default)]
+        dead_letter_file: Option<PathBuf>,
+        #[serde(default)]
+        skip_if_archaic: bool,
+        #[serde(default)]
+        min_chars_to_translate: usize,
+        #[serde(default)]
+        fallback_to_source: bool,
+        #[serde(default)]
+        style: Style,
+        #[serde(default)]
+        clean_output: bool,
+        #[serde(default)]
+        title_case: bool,
+        #[serde(default)]
+        fix_yoda_output: bool,
+    }
+
+    #[derive(Deserialize)]
+    struct BatchOptions {
+        #[serde(default)]
+        allow_empty: bool,
+    }
+
+    #[derive(Deserialize)]
+    struct RateLimitOptions {
+        per_key_per_hour: u32,
+        #[serde(default)]
+        keys: Vec<String>,
+    }
+
+    #[derive(Deserialize)]
+    struct LoadShedOptions {
+        miss_rate_threshold: f64,
+        #[serde(default = "default_loadshed_window_secs")]
+        window_secs: u64,
+        #[serde(default = "default_loadshed_min_requests")]
+        min_requests: u64,
+    }
+
+    fn default_loadshed_window_secs() -> u64 {
+        60
+    }
+
+    fn default_loadshed_min_requests() -> u64 {
+        100
+    }
+
+    let translator_options = get_table::<TranslatorOptions>(cfg, "funtranslations")?;
+    let http_options = get_table::<HttpOptions>(cfg, "http")?;
+    let metrics_options = get_table::<MetricsOptions>(cfg, "metrics")?;
+
+    let options = Options {
+        include_timestamp: get_bool_or(cfg, "include_timestamp", false)?,
+        include_names: get_bool_or(cfg, "include_names", false)?,
+        include_reading_time: get_bool_or(cfg, "include_reading_time", false)?,
+        reading_wpm: match cfg.get_int("reading_wpm") {
+            Ok(n) if n > 0 => n as u64,
+            Ok(n) => bail!("Invalid reading speed {}", n),
+            Err(ConfigError::Missing(_)) => 200,
+            Err(e) => return Err(e.into()),
+        },
+        include_source_url: get_bool_or(cfg, "include_source_url", false)?,
+        strip_apostrophes: get_bool_or(cfg, "strip_apostrophes", false)?,
+        normalize_forms: get_bool_or(cfg, "normalize_forms", false)?,
+        aliases: get_table::<HashMap<Alpha, Alpha>>(cfg, "aliases")?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(alias, name)| (alias.canonical(), name.canonical()))
+            .collect(),
+        include_aliases: get_bool_or(cfg, "include_aliases", false)?,
+        cache_unknown_names: get_bool_or(cfg, "cache_unknown_names", true)?,
+        empty_description: match cfg.get_string("empty_description") {
+            Ok(s) => Some(s),
+            Err(ConfigError::Missing(_)) => None,
+            Err(e) => return Err(e.into()),
+        },
+        max_repeated_chars: match cfg.get_int("max_repeated_chars") {
+            Ok(n) if n > 0 => Some(n as usize),
+            Ok(n) => bail!("Invalid max repeated chars {}", n),
+            Err(ConfigError::Missing(_)) => None,
+            Err(e) => return Err(e.into()),
+        },
+        debug: get_table::<DebugOptions>(cfg, "debug")?.map_or(false, |d| d.enabled),
+        chunk_size: match cfg.get_int("chunk_size") {
+            Ok(n) if n > 0 => n as usize,
+            Ok(n) => bail!("Invalid chunk size {}", n),
+            Err(ConfigError::Missing(_)) => 280,
+            Err(e) => return Err(e.into()),
+        },
+        skip_if_archaic: translator_options
+            .as_ref()
+            .map_or(false, |o| o.skip_if_archaic),
+        min_chars_to_translate: translator_options
+            .as_ref()
+            .map_or(0, |o| o.min_chars_to_translate),
+        fallback_to_source: translator_options
+            .as_ref()
+            .map_or(false, |o| o.fallback_to_source),
+        style: translator_options
+            .as_ref()
+            .map_or_else(Style::default, |o| o.style),
+        clean_output: translator_options
+            .as_ref()
+            .map_or(false, |o| o.clean_output),
+        title_case: translator_options.as_ref().map_or(false, |o| o.title_case),
+        fix_yoda_output: translator_options
+            .as_ref()
+            .map_or(false, |o| o.fix_yoda_output),
+        range_requests: http_options.as_ref().map_or(false, |o| o.range_requests),
+        msgpack: http_options.as_ref().map_or(false, |o| o.msgpack),
+        options_method: http_options.as_ref().map_or(false, |o| o.options_method),
+        root_key: http_options.as_ref().and_then(|o| o.root_key.clone()),
+        metrics: metrics_options.as_ref().map_or(false, |m| m.enabled),
+        latency_percentiles: metrics_options
+            .as_ref()
+            .map_or(false, |m| m.latency_percentiles),
+        static_descriptions: get_table::<PokeApiOptions>(cfg, "pokeapi")?
+            .map(|o| o.static_descriptions)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, description)| (name.canonical().into(), description))
+            .collect(),
+        batch_allow_empty: get_table::<BatchOptions>(cfg, "batch")?
+            .map_or(false, |b| b.allow_empty),
+    };
+
+    let canonical_names = match cfg.get_int("canonical_cache_size") {
+        Ok(0) | Err(ConfigError::Missing(_)) => CanonicalNames::disabled(),
+        Ok(size) if size > 0 => CanonicalNames::new(size as usize),
+        Ok(size) => bail!("Invalid canonical cache size {}", size),
+        Err(e) => return Err(e.into()),
+    };
 
-        Ok(rocket.manage(cache).manage(pokeapi).manage(translator))
+    let dead_letters = match translator_options.and_then(|o| o.dead_letter_file) {
+        Some(path) => DeadLetterLog::open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?,
+        None => DeadLetterLog::disabled(),
+    };
+
+    let load_shedder = match get_table::<LoadShedOptions>(cfg, "loadshed")? {
+        Some(opts) => {
+            if !(0.0..=1.0).contains(&opts.miss_rate_threshold) {
+                bail!("Invalid miss rate threshold {}", opts.miss_rate_threshold);
+            }
+            if opts.window_secs == 0 {
+                bail!("Invalid load shedding window {}", opts.window_secs);
+            }
+            LoadShedder::new(
+                opts.miss_rate_threshold,
+                Duration::from_secs(opts.window_secs),
+                opts.min_requests,
+            )
+        }
+        None => LoadShedder::disabled(),
+    };
+
+    let header_limits = HeaderLimits {
+        max_header_bytes: http_options.as_ref().and_then(|h| h.max_header_bytes),
+        max_headers: http_options.as_ref().and_then(|h| h.max_headers),
+    };
+
+    let inflight_limiter = match http_options.and_then(|h| h.max_inflight_requests) {
+        Some(0) => bail!("Invalid max inflight requests 0"),
+        Some(max) => InflightLimiter::new(max),
+        None => InflightLimiter::unlimited(),
+    };
+
+    let rate_limiter = match get_table::<RateLimitOptions>(cfg, "ratelimit")? {
+        Some(opts) if opts.per_key_per_hour == 0 => bail!("Invalid rate limit 0"),
+        Some(opts) => RateLimiter::new(opts.per_key_per_hour, opts.keys),
+        None => RateLimiter::unlimited(),
+    };
+
+    let known_names = match cfg.get_string("known_names_file") {
+        Ok(path) => KnownNames::from_file(&path).unwrap_or_else(|e| {
+            warn!(
+                "Failed to read known names from {}, not filtering names: {}",
+                path, e
+            );
+            KnownNames::unavailable()
+        }),
+        Err(ConfigError::Missing(_)) => KnownNames::unavailable(),
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(OptionsState {
+        options,
+        canonical_names,
+        dead_letters,
+        load_shedder,
+        inflight_limiter,
+        rate_limiter,
+        header_limits,
+        admin_token: AdminToken(get_table::<AdminOptions>(cfg, "admin")?.and_then(|a| a.token)),
+        known_names,
+    })
+}
+
+/// Reads a boolean under `key`, returns `default` if it's missing.
+fn get_bool_or(cfg: &Config, key: &str, default: bool) -> Result<bool, ConfigError> {
+    match cfg.get_bool(key) {
+        Err(ConfigError::Missing(_)) => Ok(default),
+        r => r,
+    }
+}
+
+/// Parses the table under `key`, returns `Ok(None)` if it's missing.
+fn get_table<T>(cfg: &Config, key: &str) -> Result<Option<T>, ConfigError>
+where
+    T: DeserializeOwned,
+{
+    match cfg.get_extra(key) {
+        Ok(v) => v.clone().try_into().map(Some).map_err(|e| {
+            ConfigError::ParseError("".into(), "Rocket.toml".into(), e.to_string(), e.line_col())
+        }),
+        Err(ConfigError::Missing(_)) => Ok(None),
+        Err(e) => Err(e),
     }
 }
 
 #[derive(Clone, Debug)]
 pub enum PokeApiConfig {
     Mock(HashMap<String, String>),
-    Concrete { url: Option<String> },
+    Concrete {
+        url: Option<ServiceUrl>,
+        path_template: Option<String>,
+        flavor_text_strategy: Option<FlavorTextStrategy>,
+        language: Option<String>,
+    },
+}
+
+impl Default for PokeApiConfig {
+    fn default() -> Self {
+        PokeApiConfig::Concrete {
+            url: None,
+            path_template: None,
+            flavor_text_strategy: None,
+            language: None,
+        }
+    }
 }
 
 impl PokeApiConfig {
-    pub fn into_client(self) -> BoxedPokeApi {
+    /// Describes the effective configuration, including defaults.
+    pub fn describe(&self) -> serde_json::Value {
+        match self {
+            PokeApiConfig::Mock(map) => json!({ "mock": map }),
+            PokeApiConfig::Concrete {
+                url,
+                path_template,
+                flavor_text_strategy,
+                language,
+            } => {
+                let default = PokeApiClient::default();
+                json!({
+                    "url": url.as_ref().map_or(default.url.as_str(), ServiceUrl::as_str),
+                    "path_template": path_template.as_ref().unwrap_or(&default.path_template),
+                    "flavor_text_strategy":
+                        flavor_text_strategy.unwrap_or(default.flavor_text_strategy),
+                    "language": language.as_ref().unwrap_or(&default.language),
+                })
+            }
+        }
+    }
+
+    /// Overrides the URL with the `POKEAPI_URL` variable, as returned by `var`. Fails if it isn't
+    /// a valid service URL.
+    pub fn with_env_overrides<F>(self, var: F) -> anyhow::Result<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let url = match var("POKEAPI_URL") {
+            Some(url) => url,
+            None => return Ok(self),
+        };
+        match self {
+            PokeApiConfig::Mock(_) => {
+                warn!("Ignoring POKEAPI_URL since PokeAPI is mocked");
+                Ok(self)
+            }
+            PokeApiConfig::Concrete {
+                path_template,
+                flavor_text_strategy,
+                language,
+                ..
+            } => Ok(PokeApiConfig::Concrete {
+                url: Some(url.parse().context("Invalid POKEAPI_URL")?),
+                path_template,
+                flavor_text_strategy,
+                language,
+            }),
+        }
+    }
+
+    /// Instantiates the configured client, making requests as set in `client_options`.
+    pub fn into_client(self, client_options: &ClientOptions) -> BoxedPokeApi {
         match self {
-            PokeApiConfig::Mock(map) => Box::new(move |s: &str| Ok(map.get(s).cloned())),
-            PokeApiConfig::Concrete { url } => {
+            PokeApiConfig::Mock(map) => Box::new(move |s: &str| {
+                Ok(map.get(s).cloned().map_or(Lookup::Missing, Lookup::Found))
+            }),
+            PokeApiConfig::Concrete {
+                url,
+                path_template,
+                flavor_text_strategy,
+                language,
+            } => {
                 let mut api = PokeApiClient::default();
                 if let Some(u) = url {
-                    api.url = u;
+                    api.url = u.into_string("pokeapi.url");
+                }
+                if let Some(t) = path_template {
+                    api.path_template = t;
+                }
+                if let Some(s) = flavor_text_strategy {
+                    api.flavor_text_strategy = s;
+                }
+                if let Some(l) = language {
+                    api.language = l;
                 }
-                Box::new(api)
+                Box::new(api.with_client_options(client_options))
             }
         }
     }
@@ -119,38 +933,157 @@ impl<'de> Deserialize<'de> for PokeApiConfig {
             mock: Option<HashMap<Alpha, String>>,
             #[serde(default)]
             url: Option<String>,
+            #[serde(default)]
+            path_template: Option<String>,
+            #[serde(default)]
+            flavor_text_strategy: Option<FlavorTextStrategy>,
+            #[serde(default)]
+            language: Option<String>,
         }
 
         let raw = RawConfig::deserialize(deserializer)?;
+        if let Some(ref t) = raw.path_template {
+            if !t.contains("{name}") {
+                return Err(D::Error::invalid_value(
+                    Unexpected::Str(t),
+                    &"a path template containing {name}",
+                ));
+            }
+        }
         match raw.mock {
             Some(map) => Ok(PokeApiConfig::Mock(
                 map.into_iter().map(|(k, v)| (k.into(), v)).collect(),
             )),
-            None => Ok(PokeApiConfig::Concrete { url: raw.url }),
+            None => Ok(PokeApiConfig::Concrete {
+                url: raw.url.map(parse_service_url).transpose()?,
+                path_template: raw.path_template,
+                flavor_text_strategy: raw.flavor_text_strategy,
+                language: raw.language,
+            }),
         }
     }
 }
 
+const DEFAULT_MOCK_FORMAT: &str = "MOCKED TRANSLATION: {text}";
+const MAX_TRANSLATION_ATTEMPTS: u32 = 10;
+
 #[derive(Clone, Debug)]
 pub enum TranslatorConfig {
-    Mock,
+    Mock {
+        format: String,
+    },
     Concrete {
-        url: Option<String>,
+        url: Option<ServiceUrl>,
         api_key: Option<String>,
+        max_attempts: Option<u32>,
+        retry_base_delay_ms: Option<u64>,
+        max_retry_delay_ms: Option<u64>,
     },
 }
 
+impl Default for TranslatorConfig {
+    fn default() -> Self {
+        TranslatorConfig::Concrete {
+            url: None,
+            api_key: None,
+            max_attempts: None,
+            retry_base_delay_ms: None,
+            max_retry_delay_ms: None,
+        }
+    }
+}
+
 impl TranslatorConfig {
-    pub fn into_translator(self) -> BoxedTranslator {
+    /// Describes the effective configuration, including defaults. The API key is redacted.
+    pub fn describe(&self) -> serde_json::Value {
+        match self {
+            TranslatorConfig::Mock { format } => json!({ "mock": true, "mock_format": format }),
+            TranslatorConfig::Concrete {
+                url,
+                api_key,
+                max_attempts,
+                retry_base_delay_ms,
+                max_retry_delay_ms,
+            } => {
+                let default = FunTranslationsApi::default();
+                json!({
+                    "mock": false,
+                    "url": url.as_ref().map_or(default.url.as_str(), ServiceUrl::as_str),
+                    "api_key": api_key.as_ref().map(|_| "<redacted>"),
+                    "max_attempts": max_attempts.unwrap_or(default.max_attempts),
+                    "retry_base_delay_ms": retry_base_delay_ms
+                        .unwrap_or(default.retry_base_delay.as_millis() as u64),
+                    "max_retry_delay_ms": max_retry_delay_ms
+                        .unwrap_or(default.max_retry_delay.as_millis() as u64),
+                })
+            }
+        }
+    }
+
+    /// Overrides the URL and API key with the `FUNTRANSLATIONS_URL` and `FUNTRANSLATIONS_API_KEY`
+    /// variables, as returned by `var`. Fails if the URL isn't a valid service URL.
+    pub fn with_env_overrides<F>(self, var: F) -> anyhow::Result<Self>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let env_url = var("FUNTRANSLATIONS_URL");
+        let env_api_key = var("FUNTRANSLATIONS_API_KEY");
+        match self {
+            TranslatorConfig::Mock { .. } => {
+                if env_url.is_some() || env_api_key.is_some() {
+                    warn!("Ignoring Fun Translations environment variables since it's mocked");
+                }
+                Ok(self)
+            }
+            TranslatorConfig::Concrete {
+                url,
+                api_key,
+                max_attempts,
+                retry_base_delay_ms,
+                max_retry_delay_ms,
+            } => Ok(TranslatorConfig::Concrete {
+                url: match env_url {
+                    Some(u) => Some(u.parse().context("Invalid FUNTRANSLATIONS_URL")?),
+                    None => url,
+                },
+                api_key: env_api_key.or(api_key),
+                max_attempts,
+                retry_base_delay_ms,
+                max_retry_delay_ms,
+            }),
+        }
+    }
+
+    /// Instantiates the configured translator, making requests as set in `client_options`.
+    pub fn into_translator(self, client_options: &ClientOptions) -> BoxedTranslator {
         match self {
-            TranslatorConfig::Mock => Box::new(|s: &str| Ok(format!("MOCKED TRANSLATION: {}", s))),
-            TranslatorConfig::Concrete { url, api_key } => {
+            TranslatorConfig::Mock { format } => Box::new(move |s: &str| {
+                Ok(format
+                    .replace("{chars}", &s.chars().count().to_string())
+                    .replace("{text}", s))
+            }),
+            TranslatorConfig::Concrete {
+                url,
+                api_key,
+                max_attempts,
+                retry_base_delay_ms,
+                max_retry_delay_ms,
+            } => {
                 let mut api = FunTranslationsApi::default();
                 api.api_key = api_key;
+                if let Some(n) = max_attempts {
+                    api.max_attempts = n;
+                }
+                if let Some(ms) = retry_base_delay_ms {
+                    api.retry_base_delay = Duration::from_millis(ms);
+                }
+                if let Some(ms) = max_retry_delay_ms {
+                    api.max_retry_delay = Duration::from_millis(ms);
+                }
                 if let Some(u) = url {
-                    api.url = u;
+                    api.url = u.into_string("funtranslations.url");
                 }
-                Box::new(api)
+                Box::new(api.with_client_options(client_options))
             }
         }
     }
@@ -163,21 +1096,318 @@ impl<'de> Deserialize<'de> for TranslatorConfig {
     {
         #[derive(Deserialize)]
         struct RawConfig {
+            #[serde(default)]
             mock: bool,
             #[serde(default)]
+            mock_format: Option<String>,
+            #[serde(default)]
             url: Option<String>,
             #[serde(default)]
             api_key: Option<String>,
+            #[serde(default)]
+            max_attempts: Option<u32>,
+            #[serde(default)]
+            retry_base_delay_ms: Option<u64>,
+            #[serde(default)]
+            max_retry_delay_ms: Option<u64>,
         }
 
         let raw = RawConfig::deserialize(deserializer)?;
+        match raw.max_attempts {
+            Some(0) => {
+                return Err(D::Error::invalid_value(
+                    Unexpected::Unsigned(0),
+                    &"at least one attempt",
+                ))
+            }
+            Some(n) if n > MAX_TRANSLATION_ATTEMPTS => {
+                return Err(D::Error::invalid_value(
+                    Unexpected::Unsigned(n.into()),
+                    &"at most 10 attempts",
+                ))
+            }
+            _ => {}
+        }
         if raw.mock {
-            Ok(TranslatorConfig::Mock)
+            Ok(TranslatorConfig::Mock {
+                format: raw
+                    .mock_format
+                    .unwrap_or_else(|| DEFAULT_MOCK_FORMAT.to_string()),
+            })
         } else {
             Ok(TranslatorConfig::Concrete {
-                url: raw.url,
+                url: raw.url.map(parse_service_url).transpose()?,
                 api_key: raw.api_key,
+                max_attempts: raw.max_attempts,
+                retry_base_delay_ms: raw.retry_base_delay_ms,
+                max_retry_delay_ms: raw.max_retry_delay_ms,
             })
         }
     }
 }
+
+/// URL of an external service, must be absolute and use either http or https.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServiceUrl(Url);
+
+impl ServiceUrl {
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
+    pub fn is_secure(&self) -> bool {
+        self.0.scheme() == "https"
+    }
+
+    /// Returns the canonical form of the URL, warning if it's insecure. `key` is the config key
+    /// the URL was read from.
+    pub fn into_string(self, key: &str) -> String {
+        if !self.is_secure() {
+            warn!("{} doesn't use https: {}", key, self.0);
+        }
+        self.0.into_string()
+    }
+}
+
+impl FromStr for ServiceUrl {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Url::parse(s) {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => Ok(ServiceUrl(url)),
+            _ => bail!("{:?} isn't an http or https URL", s),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ServiceUrl {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        parse_service_url(String::deserialize(deserializer)?)
+    }
+}
+
+/// Parses a `ServiceUrl` while deserializing, so mocked services can skip validating their URL.
+fn parse_service_url<E: serde::de::Error>(s: String) -> Result<ServiceUrl, E> {
+    s.parse()
+        .map_err(|_| E::invalid_value(Unexpected::Str(&s), &"an http or https URL"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rocket::config::{Environment, Value};
+    use rocket::error::LaunchErrorKind;
+    use rocket::local::Client;
+
+    use crate::test_utils::MockServer;
+
+    #[test]
+    fn test_default_options() {
+        let config = Config::build(Environment::Development).finalize().unwrap();
+        let options = read_options(&config).unwrap().options;
+        assert_eq!(
+            serde_json::to_value(options).unwrap(),
+            serde_json::to_value(Options::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_service_url() {
+        let url: ServiceUrl = Value::from("https://pokeapi.co").try_into().unwrap();
+        assert!(url.is_secure());
+        assert_eq!(url.into_string("url"), "https://pokeapi.co/");
+
+        let url: ServiceUrl = Value::from("http://localhost:8080/api/")
+            .try_into()
+            .unwrap();
+        assert!(!url.is_secure());
+
+        for invalid in &["htps://pokeapi.co", "pokeapi.co", "not a url", ""] {
+            Value::from(*invalid).try_into::<ServiceUrl>().unwrap_err();
+        }
+    }
+
+    #[test]
+    fn test_mock_translation_format() {
+        let mut funtranslations = HashMap::new();
+        funtranslations.insert("mock", Value::from(true));
+        let translator: TranslatorConfig = Value::from(funtranslations.clone()).try_into().unwrap();
+        assert_eq!(
+            translator
+                .into_translator(&ClientOptions::default())
+                .translate("Hello")
+                .unwrap(),
+            "MOCKED TRANSLATION: Hello"
+        );
+
+        funtranslations.insert("mock_format", Value::from("{text} ({chars} chars)"));
+        let translator: TranslatorConfig = Value::from(funtranslations).try_into().unwrap();
+        assert_eq!(
+            translator
+                .into_translator(&ClientOptions::default())
+                .translate("Pokémon")
+                .unwrap(),
+            "Pokémon (7 chars)"
+        );
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let server = MockServer::start(|_| (200, r#"{"contents":{"translated":"Hi"}}"#.into()));
+        let env: HashMap<_, _> = vec![
+            (
+                "POKEAPI_URL",
+                "https://pokeapi.example/species/".to_string(),
+            ),
+            ("FUNTRANSLATIONS_URL", format!("{}/shakespeare", server.url)),
+            ("FUNTRANSLATIONS_API_KEY", "env-secret".to_string()),
+        ]
+        .into_iter()
+        .collect();
+        let var = |k: &str| env.get(k).cloned();
+
+        let mut pokeapi = HashMap::new();
+        pokeapi.insert(
+            "url",
+            Value::from("https://pokeapi.co/api/v2/pokemon-species/"),
+        );
+        let pokeapi: PokeApiConfig = Value::from(pokeapi).try_into().unwrap();
+        let pokeapi = pokeapi.with_env_overrides(var).unwrap();
+        assert_eq!(
+            pokeapi.describe()["url"],
+            json!("https://pokeapi.example/species/")
+        );
+
+        let mut funtranslations = HashMap::new();
+        funtranslations.insert("api_key", Value::from("toml-secret"));
+        let translator: TranslatorConfig = Value::from(funtranslations).try_into().unwrap();
+        let translator = translator
+            .with_env_overrides(var)
+            .unwrap()
+            .into_translator(&ClientOptions::default());
+        assert_eq!(translator.translate("Hello").unwrap(), "Hi");
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/shakespeare");
+        assert_eq!(
+            requests[0].header("X-FunTranslations-Api-Secret"),
+            Some("env-secret")
+        );
+
+        // Unset variables keep the configured values
+        let pokeapi = PokeApiConfig::default()
+            .with_env_overrides(|_| None)
+            .unwrap();
+        assert_eq!(
+            pokeapi.describe()["url"],
+            json!(PokeApiClient::default().url)
+        );
+        let invalid = |_: &str| Some("pokeapi.co".to_string());
+        PokeApiConfig::default()
+            .with_env_overrides(invalid)
+            .unwrap_err();
+        TranslatorConfig::default()
+            .with_env_overrides(invalid)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_invalid_url_fails_startup() {
+        let mut pokeapi = HashMap::new();
+        pokeapi.insert("url", "htps://pokeapi.co/api/v2/pokemon-species/");
+        let config = Config::build(Environment::Development)
+            .extra("pokeapi", pokeapi)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).attach(ReadConfig);
+        let err = Client::new(rocket).err().expect("Launch should fail");
+        assert!(matches!(err.kind(), LaunchErrorKind::FailedFairings(_)));
+
+        let mut funtranslations = HashMap::new();
+        funtranslations.insert("url", "not a url");
+        let config = Config::build(Environment::Development)
+            .extra("funtranslations", funtranslations)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).attach(ReadConfig);
+        let err = Client::new(rocket).err().expect("Launch should fail");
+        assert!(matches!(err.kind(), LaunchErrorKind::FailedFairings(_)));
+    }
+
+    #[test]
+    fn test_require_reachable() {
+        let make_rocket = |fallback_to_source: bool| {
+            let mut funtranslations = HashMap::new();
+            // Nothing listens on port 1, so connections are refused
+            funtranslations.insert("url", Value::from("http://127.0.0.1:1/shakespeare"));
+            funtranslations.insert("require_reachable", Value::from(true));
+            funtranslations.insert("fallback_to_source", Value::from(fallback_to_source));
+            let config = Config::build(Environment::Development)
+                .extra("funtranslations", funtranslations)
+                .finalize()
+                .unwrap();
+            rocket::custom(config).attach(ReadConfig)
+        };
+
+        let err = Client::new(make_rocket(false))
+            .err()
+            .expect("Launch should fail");
+        assert!(matches!(err.kind(), LaunchErrorKind::FailedFairings(_)));
+        Client::new(make_rocket(true)).expect("Launch should succeed");
+    }
+
+    #[test]
+    fn test_max_attempts_bounds() {
+        let parse = |n: u32| {
+            serde_json::from_value::<TranslatorConfig>(json!({ "max_attempts": n }))
+                .map(|_| ())
+                .map_err(|e| e.to_string())
+        };
+        assert!(parse(0).unwrap_err().contains("at least one attempt"));
+        parse(1).unwrap();
+        parse(10).unwrap();
+        assert!(parse(11).unwrap_err().contains("at most 10 attempts"));
+        assert!(parse(u32::MAX).is_err());
+    }
+
+    #[test]
+    fn test_valid_urls_launch() {
+        let mut pokeapi = HashMap::new();
+        pokeapi.insert("url", "http://localhost:8080/api/v2/pokemon-species/");
+        let mut funtranslations = HashMap::new();
+        funtranslations.insert(
+            "url",
+            "https://api.funtranslations.com/translate/shakespeare/",
+        );
+        let config = Config::build(Environment::Development)
+            .extra("pokeapi", pokeapi)
+            .extra("funtranslations", funtranslations)
+            .finalize()
+            .unwrap();
+        Client::new(rocket::custom(config).attach(ReadConfig)).expect("Launch should succeed");
+
+        // Mocked services never request their URL, so it isn't validated
+        let mut pokeapi = HashMap::new();
+        pokeapi.insert("url", Value::from("garbage"));
+        pokeapi.insert(
+            "mock",
+            Value::from(
+                vec![("pikachu", "desc")]
+                    .into_iter()
+                    .collect::<HashMap<_, _>>(),
+            ),
+        );
+        let mut funtranslations = HashMap::new();
+        funtranslations.insert("url", Value::from("garbage"));
+        funtranslations.insert("mock", Value::from(true));
+        let config = Config::build(Environment::Development)
+            .extra("pokeapi", pokeapi)
+            .extra("funtranslations", funtranslations)
+            .finalize()
+            .unwrap();
+        Client::new(rocket::custom(config).attach(ReadConfig)).expect("Launch should succeed");
+    }
+}
@@ -0,0 +1,101 @@
+//! GraphQL endpoint, enabled with the `graphql` feature. Resolves descriptions through the same
+//! pipeline as `/pokemon/<name>`.
+use juniper::{EmptyMutation, FieldError, FieldResult, GraphQLObject, RootNode};
+use juniper_rocket::{GraphQLRequest, GraphQLResponse};
+use log::error;
+use rocket::http::Status;
+use rocket::post;
+
+use crate::api::Error as ApiError;
+use crate::{describe, Pipeline, PokemonQuery};
+
+/// Translated pokemon, as exposed in the GraphQL schema.
+#[derive(GraphQLObject)]
+struct Pokemon {
+    name: String,
+    description: String,
+}
+
+struct Query<'a> {
+    pipeline: &'a Pipeline<'a>,
+}
+
+#[juniper::object]
+impl<'a> Query<'a> {
+    /// Shakespearean description of a pokemon, null if it doesn't exist.
+    fn pokemon(&self, name: String) -> FieldResult<Option<Pokemon>> {
+        match describe(self.pipeline, &name, &PokemonQuery::default(), true) {
+            Ok((pokemon, _)) => Ok(Some(Pokemon {
+                name: pokemon.name,
+                description: pokemon.description,
+            })),
+            Err(ApiError::Status(Status::NotFound))
+            | Err(ApiError::NotFound(_))
+            | Err(ApiError::NotFoundSuggesting(..)) => Ok(None),
+            Err(ApiError::Status(status)) => Err(FieldError::from(status.reason)),
+            Err(ApiError::Invalid(_)) => Err(FieldError::from(Status::BadRequest.reason)),
+            Err(ApiError::RateLimited(_)) => Err(FieldError::from(Status::TooManyRequests.reason)),
+            Err(ApiError::Other(e)) => {
+                error!("{}", e);
+                Err(FieldError::from(Status::InternalServerError.reason))
+            }
+        }
+    }
+}
+
+type Schema<'a> = RootNode<'static, Query<'a>, EmptyMutation<()>>;
+
+#[post("/graphql", data = "<request>")]
+pub(crate) fn graphql(pipeline: Pipeline, request: GraphQLRequest) -> GraphQLResponse {
+    let schema = Schema::new(
+        Query {
+            pipeline: &pipeline,
+        },
+        EmptyMutation::new(),
+    );
+    request.execute(&schema, &())
+}
+
+#[cfg(test)]
+mod test {
+    use rocket::config::{Config, Environment};
+    use rocket::http::{ContentType, Status};
+    use rocket::local::Client;
+    use serde_json::json;
+
+    use crate::services::Lookup;
+    use crate::RocketExt;
+
+    #[test]
+    fn test_graphql_matches_rest() {
+        let rocket = rocket::custom(Config::new(Environment::Development)).poke_shakespeare_custom(
+            |name: &str| match name {
+                "foo" => Ok(Lookup::Found("desc foo".to_string())),
+                _ => Ok(Lookup::Missing),
+            },
+            |source: &str| Ok(format!("TRANSLATED: {}", source)),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let mut response = client.get("/pokemon/foo").dispatch();
+        let rest: serde_json::Value =
+            serde_json::from_slice(&response.body_bytes().unwrap()).unwrap();
+
+        let mut response = client
+            .post("/graphql")
+            .header(ContentType::JSON)
+            .body(
+                json!({
+                    "query": r#"{ foo: pokemon(name: "foo") { name description } bar: pokemon(name: "bar") { name } }"#,
+                })
+                .to_string(),
+            )
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let graphql: serde_json::Value =
+            serde_json::from_slice(&response.body_bytes().unwrap()).unwrap();
+        assert_eq!(graphql["data"]["foo"]["name"], rest["name"]);
+        assert_eq!(graphql["data"]["foo"]["description"], rest["description"]);
+        assert_eq!(graphql["data"]["bar"], serde_json::Value::Null);
+    }
+}
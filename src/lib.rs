@@ -15,17 +15,48 @@
 
 mod api;
 mod config;
+#[cfg(feature = "graphql")]
+mod graphql;
+mod library;
+mod metrics;
 pub mod services;
+#[cfg(test)]
+mod test_utils;
+mod text;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use rocket::http::{RawStr, Status};
+use log::{error, warn};
+use rocket::http::{ContentType, RawStr, Status};
+use rocket::request::{self, FromRequest, LenientForm};
+use rocket::response::content::Content;
 use rocket::response::status;
-use rocket::{get, routes, Rocket, State};
+use rocket::{
+    delete, get, options, post, routes, FromForm, Outcome, Request, Response, Rocket, Route, State,
+};
 use rocket_contrib::json::Json;
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 
-use api::{Alpha, Error as ApiError, Result as ApiResult, SerializeErrors};
-use config::ReadConfig;
-use services::{BoxedPokeApi, BoxedTranslator, Cache, PokeApi, Translator};
+pub use config::Options;
+pub use library::PokeShakespeare;
+
+use api::{
+    Admin, Alpha, Error as ApiError, HeadersWithinLimits, InflightPermit, InvalidEntry, Negotiated,
+    Ranged, RateLimitClient, RateLimited, RateLimiter, Result as ApiResult, Rooted,
+    SerializeErrors, Species, Tagged, WithinRateLimit,
+};
+use config::{ReadConfig, ReadOptions, ServicesConfig};
+use library::calculate_entry;
+use metrics::{
+    LatencySummary, Metrics, Outcome as RequestOutcome, RegisterCacheMetrics, TrackLatency,
+};
+use services::{
+    BoxedPokeApi, BoxedTranslator, Cache, CacheEntry, CacheKey, CacheLogEntry, CacheStats,
+    CanonicalNames, DeadLetterLog, DescriptionSource, KnownNames, LoadShedder, PokeApi,
+    SpeciesNames, Translator,
+};
 
 /// Extends `Rocket` instances to serve the poke_shakespeare API.
 pub trait RocketExt {
@@ -42,7 +73,13 @@ impl RocketExt for Rocket {
     fn poke_shakespeare(self) -> Self {
         self.attach(SerializeErrors)
             .attach(ReadConfig)
-            .mount("/", routes![pokemon, pokemon_badrequest])
+            .attach(ReadOptions)
+            .attach(TrackLatency)
+            .manage(Metrics::default())
+            .attach(RegisterCacheMetrics)
+            .manage(Liveness::default())
+            .manage(SpeciesNames::default())
+            .mount("/", routes())
     }
 
     fn poke_shakespeare_custom<P, T>(self, pokeapi: P, translator: T) -> Self
@@ -51,62 +88,770 @@ impl RocketExt for Rocket {
         T: 'static + Translator + Send + Sync,
     {
         self.attach(SerializeErrors)
+            .attach(ReadOptions)
+            .attach(TrackLatency)
             .manage(BoxedPokeApi::from(Box::new(pokeapi)))
             .manage(BoxedTranslator::from(Box::new(translator)))
             .manage(Cache::new(1))
-            .mount("/", routes![pokemon, pokemon_badrequest])
+            .manage(Metrics::default())
+            .attach(RegisterCacheMetrics)
+            .manage(Liveness::default())
+            .manage(SpeciesNames::default())
+            .mount("/", routes())
     }
 }
 
+/// Routes served by the application.
+fn routes() -> Vec<Route> {
+    #[allow(unused_mut)]
+    let mut routes = routes![
+        pokemon,
+        pokemon_options,
+        pokemon_batch,
+        pokemon_raw,
+        translate,
+        effective_config,
+        metrics,
+        latency_stats,
+        health,
+        healthz,
+        fail_health,
+        cache_stats,
+        cache_order,
+        cache_log,
+        purge_cache
+    ];
+    #[cfg(feature = "graphql")]
+    routes.extend(routes![graphql::graphql]);
+    routes
+}
+
 /// /pokemon response
-#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
 pub struct Pokemon {
     pub name: String,
     pub description: String,
+    /// Index of the next chunk of the description, if it was requested in chunks with `?chunk=`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_chunk: Option<usize>,
+    /// How the description was produced, omitted for regular translations.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<DescriptionSource>,
+    /// False if the description is untranslated because the translator failed, see
+    /// `funtranslations.fallback_to_source` in `ReadOptions`. Omitted otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub translated: Option<bool>,
+    /// Estimated seconds to read the description, see `include_reading_time` in `ReadOptions`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reading_time_seconds: Option<u64>,
+    /// PokeAPI resource the description was fetched from, see `include_source_url` in
+    /// `ReadOptions`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+    /// Untranslated PokeAPI description, if requested with `?include_source=true`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_description: Option<String>,
+    /// Alternate names resolving to this pokemon, see `include_aliases` in `ReadOptions`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aliases: Option<Vec<String>>,
+    /// RFC3339 timestamp of when the description was calculated, see `include_timestamp` in
+    /// `ReadOptions`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generated_at: Option<String>,
+    /// Name as sent by the client, see `include_names` in `ReadOptions`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requested_name: Option<String>,
+    /// Name after canonicalization, see `include_names` in `ReadOptions`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canonical_name: Option<String>,
+    /// Raw response of the translation service, see `debug.enabled` in `ReadOptions`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub debug: Option<serde_json::Value>,
+}
+
+/// Request guard bundling the managed state used to serve pokemon descriptions.
+struct Pipeline<'r> {
+    _headers: HeadersWithinLimits,
+    _permit: InflightPermit<'r>,
+    pokeapi: State<'r, BoxedPokeApi>,
+    translator: State<'r, BoxedTranslator>,
+    cache: State<'r, Cache>,
+    canonical_names: State<'r, CanonicalNames>,
+    known_names: State<'r, KnownNames>,
+    species_names: State<'r, SpeciesNames>,
+    dead_letters: State<'r, DeadLetterLog>,
+    load_shedder: State<'r, LoadShedder>,
+    metrics: State<'r, Metrics>,
+    options: State<'r, Options>,
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for Pipeline<'r> {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, ()> {
+        Outcome::Success(Pipeline {
+            _headers: request.guard()?,
+            _permit: request.guard()?,
+            pokeapi: request.guard()?,
+            translator: request.guard()?,
+            cache: request.guard()?,
+            canonical_names: request.guard()?,
+            known_names: request.guard()?,
+            species_names: request.guard()?,
+            dead_letters: request.guard()?,
+            load_shedder: request.guard()?,
+            metrics: request.guard()?,
+            options: request.guard()?,
+        })
+    }
+}
+
+/// Query parameters accepted by `/pokemon/<name>`.
+#[derive(Debug, Default, FromForm)]
+struct PokemonQuery {
+    /// Include the raw translation in the response, only honored with `debug.enabled`.
+    debug_translation: Option<bool>,
+    /// Index of the chunk of the description to return, see `chunk_size`.
+    chunk: Option<usize>,
+    /// Size of description chunks in characters, defaults to the `chunk_size` config.
+    chunk_size: Option<usize>,
+    /// Translate only the first sentence of the source description.
+    first_sentence: Option<bool>,
+    /// Include the untranslated description in the response.
+    include_source: Option<bool>,
+    /// Translation style, defaults to the `funtranslations.style` config.
+    style: Option<String>,
 }
 
-#[get("/pokemon/<name>")]
+#[get("/pokemon/<name>?<query..>")]
 fn pokemon(
-    pokeapi: State<BoxedPokeApi>,
-    translator: State<BoxedTranslator>,
-    cache: State<Cache>,
-    name: Alpha,
+    pipeline: Pipeline,
+    rate_limit: Result<WithinRateLimit, RateLimited>,
+    name: &RawStr,
+    query: LenientForm<PokemonQuery>,
+) -> Ranged<Tagged<Result<Negotiated<Rooted<Pokemon>>, ApiError>>> {
+    let options = &pipeline.options;
+    let mut key = None;
+    let result = rate_limit
+        .map_err(|RateLimited(delay)| ApiError::RateLimited(delay))
+        .and_then(|_| {
+            name.percent_decode()
+                .map_err(|_| ApiError::Status(Status::BadRequest))
+        })
+        .and_then(|name| {
+            describe(&pipeline, &name, &query, true)
+                .map(|(pokemon, cache_key)| {
+                    key = Some(cache_key);
+                    pokemon
+                })
+                .map_err(|e| match e {
+                    ApiError::NotFound(kind) if kind == POKEMON_NOT_FOUND => {
+                        let name = name.to_lowercase();
+                        // Names rejected by the known names file are suggested from the same list
+                        let suggestions = match pipeline.known_names.names() {
+                            Some(names) => text::suggestions(&name, names, MAX_SUGGESTIONS),
+                            None => pipeline
+                                .species_names
+                                .get(&**pipeline.pokeapi)
+                                .map_or_else(Vec::new, |names| {
+                                    text::suggestions(&name, names.iter(), MAX_SUGGESTIONS)
+                                }),
+                        };
+                        ApiError::NotFoundSuggesting(kind, suggestions)
+                    }
+                    e => e,
+                })
+        })
+        .map(|pokemon| {
+            Negotiated::new(
+                Rooted::new(pokemon, options.root_key.clone()),
+                options.msgpack,
+            )
+        });
+    // Representations differing in anything but the body still get different tags
+    let variant = (
+        key,
+        query.include_source,
+        options.include_timestamp,
+        options.include_names,
+        options.include_reading_time,
+        options.include_source_url,
+        options.include_aliases,
+    );
+    Ranged::new(Tagged::new(result, &variant), options.range_requests)
+}
+
+/// Max similar names suggested for pokemon which weren't found.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Max names accepted by `POST /pokemon`.
+const MAX_BATCH_SIZE: usize = 20;
+
+/// Item of the `POST /pokemon` response, either a pokemon or the error looking it up.
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
+pub enum BatchEntry {
+    Found(Pokemon),
+    Failed {
+        name: String,
+        status: u16,
+        error: String,
+    },
+}
+
+/// Looks up several pokemon at once, answering with an entry for each name in the same order.
+/// Failed lookups are reported in their entry instead of failing the whole batch.
+#[post("/pokemon", format = "json", data = "<names>")]
+fn pokemon_batch(
+    pipeline: Pipeline,
+    rate_limiter: State<RateLimiter>,
+    client: RateLimitClient,
+    names: Json<Vec<String>>,
+) -> ApiResult<Vec<BatchEntry>> {
+    if names.len() > MAX_BATCH_SIZE || (names.is_empty() && !pipeline.options.batch_allow_empty) {
+        return Err(ApiError::Status(Status::BadRequest));
+    }
+    // Each distinct name counts as a request
+    let distinct: HashSet<&str> = names.iter().map(|name| &**name).collect();
+    rate_limiter
+        .acquire_n(client, distinct.len() as u32)
+        .map_err(|RateLimited(delay)| ApiError::RateLimited(delay))?;
+    // Report every invalid name at once rather than failing on the first one
+    let invalid: Vec<_> = names
+        .iter()
+        .enumerate()
+        .filter_map(|(index, name)| {
+            let requested_name = requested_name(&pipeline.options, name);
+            let parsed = parse_requested_name(&pipeline.options, &requested_name);
+            parsed.err().map(|reason| InvalidEntry {
+                index,
+                value: name.clone(),
+                reason: reason.into(),
+            })
+        })
+        .collect();
+    if !invalid.is_empty() {
+        return Err(ApiError::Invalid(invalid));
+    }
+    let mut entries: HashMap<&str, BatchEntry> = HashMap::new();
+    let mut batch = Vec::with_capacity(names.len());
+    for name in names.iter() {
+        let entry = entries.entry(&**name).or_insert_with(|| {
+            match describe(&pipeline, name, &PokemonQuery::default(), true) {
+                Ok((pokemon, _)) => BatchEntry::Found(pokemon),
+                Err(e) => {
+                    let status = match e {
+                        ApiError::Status(status) => status,
+                        ApiError::NotFound(_) | ApiError::NotFoundSuggesting(..) => {
+                            Status::NotFound
+                        }
+                        ApiError::Invalid(_) => Status::BadRequest,
+                        ApiError::RateLimited(_) => Status::TooManyRequests,
+                        ApiError::Other(e) => {
+                            error!("{}", e);
+                            Status::InternalServerError
+                        }
+                    };
+                    BatchEntry::Failed {
+                        name: name.to_string(),
+                        status: status.code,
+                        error: status.reason.into(),
+                    }
+                }
+            }
+        });
+        batch.push(entry.clone());
+    }
+    Ok(Json(batch))
+}
+
+/// Looks up the english description of a pokemon without translating it. Descriptions are cached
+/// apart from translated ones.
+#[get("/pokemon/<name>/raw")]
+fn pokemon_raw(
+    pipeline: Pipeline,
+    rate_limit: Result<WithinRateLimit, RateLimited>,
+    name: &RawStr,
 ) -> ApiResult<Pokemon> {
-    let cached =
-        cache.get_or_calculate(name.clone(), || match pokeapi.get_description(&name)? {
-            Some(source_description) => translator.translate(&source_description).map(Some),
-            None => Ok(None),
-        })?;
-    match cached {
-        Some(description) => Ok(Json(Pokemon {
-            name: name.into(),
+    rate_limit.map_err(|RateLimited(delay)| ApiError::RateLimited(delay))?;
+    let name = name
+        .percent_decode()
+        .map_err(|_| ApiError::Status(Status::BadRequest))?;
+    describe(&pipeline, &name, &PokemonQuery::default(), false).map(|(pokemon, _)| Json(pokemon))
+}
+
+/// Lists the methods supported by `/pokemon/<name>`. Only available with `http.options_method`.
+#[options("/pokemon/<_name>")]
+fn pokemon_options(_name: &RawStr, options: State<Options>) -> Option<Response<'static>> {
+    if !options.options_method {
+        return None;
+    }
+    Some(
+        Response::build()
+            .status(Status::NoContent)
+            .raw_header("Allow", "GET, HEAD, OPTIONS")
+            .finalize(),
+    )
+}
+
+/// Looks up and translates the description of the pokemon `name` as requested by the client, or
+/// only cleans up its whitespace unless `translate`. Concurrent lookups of the same canonical name
+/// from either `/pokemon` endpoint share a single upstream calculation through `Cache`.
+fn describe(
+    pipeline: &Pipeline,
+    name: &str,
+    query: &PokemonQuery,
+    translate: bool,
+) -> Result<(Pokemon, CacheKey), ApiError> {
+    let mut miss = false;
+    let result = lookup(pipeline, name, query, translate, &mut miss);
+    pipeline.metrics.record_request(match result {
+        Ok(_) if miss => RequestOutcome::Miss,
+        Ok(_) => RequestOutcome::Hit,
+        Err(ApiError::Status(Status::NotFound)) | Err(ApiError::NotFound(_)) => {
+            RequestOutcome::NotFound
+        }
+        Err(_) => RequestOutcome::Error,
+    });
+    result
+}
+
+/// Name requested by the client, with apostrophes stripped if `strip_apostrophes` is set.
+fn requested_name(options: &Options, name: &str) -> String {
+    if options.strip_apostrophes {
+        text::strip_apostrophes(name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Parses a `requested_name` into a Pokédex number or a valid name, normalizing forms if
+/// `normalize_forms` is set. The name isn't canonicalized yet. Returns why it's invalid otherwise.
+fn parse_requested_name(options: &Options, requested_name: &str) -> Result<Species, &'static str> {
+    if let Some(id) = Species::parse_id(requested_name) {
+        return Ok(id);
+    }
+    let form = if options.normalize_forms {
+        text::form_slug(requested_name)
+    } else {
+        None
+    };
+    Alpha::parse(form.unwrap_or_else(|| requested_name.to_string())).map(Species::Name)
+}
+
+/// Error kind of 404 responses for pokemon which don't exist.
+const POKEMON_NOT_FOUND: &str = "pokemon_not_found";
+/// Error kind of 404 responses for pokemon without a description.
+const NO_DESCRIPTION: &str = "no_english_description";
+
+/// Implements `describe`, setting `miss` if the description wasn't cached.
+fn lookup(
+    pipeline: &Pipeline,
+    name: &str,
+    query: &PokemonQuery,
+    translate: bool,
+    miss: &mut bool,
+) -> Result<(Pokemon, CacheKey), ApiError> {
+    let Pipeline {
+        pokeapi,
+        translator,
+        cache,
+        canonical_names,
+        known_names,
+        dead_letters,
+        load_shedder,
+        metrics,
+        options,
+        ..
+    } = pipeline;
+    let requested_name = requested_name(options, name);
+    let species = match parse_requested_name(options, &requested_name)
+        .map_err(|_| ApiError::Status(Status::BadRequest))?
+    {
+        Species::Id(id) => Species::Id(id),
+        Species::Name(requested_name) => {
+            let name = canonical_names.get_or_canonicalize(&requested_name, Alpha::canonical);
+            let name = options.aliases.get(&name).cloned().unwrap_or(name);
+            if let Some(max) = options.max_repeated_chars {
+                if name.longest_run() > max {
+                    return Err(ApiError::Status(Status::BadRequest));
+                }
+            }
+            if !known_names.may_exist(&name) {
+                return Err(ApiError::NotFound(POKEMON_NOT_FOUND));
+            }
+            Species::Name(name)
+        }
+    };
+    let name = species.to_string();
+    let debug = options.debug && query.debug_translation.unwrap_or(false);
+    let chunk_size = query.chunk_size.unwrap_or(options.chunk_size);
+    if chunk_size == 0 {
+        return Err(ApiError::Status(Status::BadRequest));
+    }
+    let first_sentence = query.first_sentence.unwrap_or(false);
+    let style = match query.style {
+        Some(ref style) => style
+            .parse()
+            .map_err(|_| ApiError::Status(Status::BadRequest))?,
+        None => options.style,
+    };
+    let key = CacheKey {
+        species,
+        first_sentence,
+        style,
+        raw: !translate,
+    };
+    let mut raw_translation = None;
+    let mut shed = false;
+    let mut calculate = || {
+        if load_shedder.is_shedding() {
+            shed = true;
+            return Err(ApiError::Status(Status::ServiceUnavailable));
+        }
+        *miss = true;
+        let (entry, raw) = calculate_entry(
+            &***pokeapi,
+            &***translator,
+            dead_letters,
+            metrics,
+            options,
+            &key,
+        )?;
+        raw_translation = raw;
+        Ok(entry)
+    };
+    // Debug responses bypass the cache since raw translations aren't cached
+    let cached = if debug {
+        calculate()
+    } else {
+        cache.get_or_calculate_if(key.clone(), calculate, |entry| {
+            let known = options.cache_unknown_names
+                || entry.description.is_some()
+                || known_names.contains(&name);
+            known && entry.source != DescriptionSource::Untranslated
+        })
+    };
+    // Shed requests aren't recorded, otherwise retried misses would keep shedding going
+    if !shed {
+        load_shedder.record(!*miss);
+    }
+    let entry = match cached {
+        Err(ApiError::Other(e)) => match options.static_descriptions.get(&name) {
+            Some(description) => {
+                warn!("Serving static description for {}: {:#}", name, e);
+                CacheEntry {
+                    source: DescriptionSource::Static,
+                    ..CacheEntry::new(Some(description.clone()))
+                }
+            }
+            None => return Err(ApiError::Other(e)),
+        },
+        cached => cached?,
+    };
+    let (description, next_chunk) = match (entry.description, query.chunk) {
+        (Some(description), Some(chunk)) => {
+            match chunk_description(&description, chunk, chunk_size) {
+                Some((text, next)) => (Some(text), next),
+                None => return Err(ApiError::Status(Status::BadRequest)),
+            }
+        }
+        (description, _) => (description, None),
+    };
+    let pokemon = match description {
+        Some(description) => Pokemon {
+            name: name.clone(),
+            reading_time_seconds: if options.include_reading_time {
+                Some(text::reading_time_secs(&description, options.reading_wpm))
+            } else {
+                None
+            },
+            source_url: if options.include_source_url {
+                pokeapi.source_url(&name)
+            } else {
+                None
+            },
+            source_description: if query.include_source.unwrap_or(false) {
+                entry.source_description
+            } else {
+                None
+            },
+            aliases: if options.include_aliases {
+                let mut aliases: Vec<_> = options
+                    .aliases
+                    .iter()
+                    .filter(|(_, target)| ***target == *name)
+                    .map(|(alias, _)| alias.to_string())
+                    .collect();
+                aliases.sort();
+                Some(aliases)
+            } else {
+                None
+            },
             description,
-        })),
-        None => Err(ApiError::Status(Status::NotFound)),
+            next_chunk,
+            translated: match entry.source {
+                DescriptionSource::Untranslated => Some(false),
+                _ => None,
+            },
+            source: match entry.source {
+                DescriptionSource::Translated
+                | DescriptionSource::Untranslated
+                | DescriptionSource::Raw => None,
+                source => Some(source),
+            },
+            generated_at: if options.include_timestamp {
+                Some(humantime::format_rfc3339_millis(entry.created_at).to_string())
+            } else {
+                None
+            },
+            requested_name: if options.include_names {
+                Some(requested_name)
+            } else {
+                None
+            },
+            canonical_name: if options.include_names {
+                Some(name)
+            } else {
+                None
+            },
+            debug: if debug { raw_translation } else { None },
+        },
+        None if entry.no_description => return Err(ApiError::NotFound(NO_DESCRIPTION)),
+        None => return Err(ApiError::NotFound(POKEMON_NOT_FOUND)),
+    };
+    Ok((pokemon, key))
+}
+
+/// Returns the `chunk`th slice of `size` characters of the description, and the index of the next
+/// chunk if there is one. Returns `None` if the chunk is out of range.
+fn chunk_description(
+    description: &str,
+    chunk: usize,
+    size: usize,
+) -> Option<(String, Option<usize>)> {
+    let len = description.chars().count();
+    let start = chunk.checked_mul(size)?;
+    if start >= len && !(chunk == 0 && len == 0) {
+        return None;
+    }
+    let text = description.chars().skip(start).take(size).collect();
+    let next = if start + size < len {
+        Some(chunk + 1)
+    } else {
+        None
+    };
+    Some((text, next))
+}
+
+/// `/translate` request body.
+#[derive(Debug, Deserialize)]
+struct TranslateRequest {
+    text: String,
+    /// Translation style, defaults to the `funtranslations.style` config.
+    #[serde(default)]
+    style: Option<String>,
+}
+
+/// `/translate` response.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+struct Translation {
+    translation: String,
+}
+
+/// Translates arbitrary text, bypassing PokeAPI and the cache. Only available with
+/// `debug.enabled`.
+#[post("/translate", format = "json", data = "<request>")]
+fn translate(
+    translator: State<BoxedTranslator>,
+    options: State<Options>,
+    request: Json<TranslateRequest>,
+) -> ApiResult<Translation> {
+    if !options.debug {
+        return Err(ApiError::Status(Status::NotFound));
+    }
+    let style = match request.style {
+        Some(ref style) => style
+            .parse()
+            .map_err(|_| ApiError::Status(Status::BadRequest))?,
+        None => options.style,
+    };
+    let (translation, _) = translator.translate_styled(&request.text, style)?;
+    Ok(Json(Translation { translation }))
+}
+
+/// Request and cache metrics in the Prometheus text format. Only available with
+/// `metrics.enabled`.
+#[get("/metrics")]
+fn metrics(options: State<Options>, metrics: State<Metrics>) -> Option<Content<String>> {
+    if !options.metrics {
+        return None;
+    }
+    Some(Content(
+        ContentType::with_params("text", "plain", ("version", "0.0.4")),
+        metrics.render(),
+    ))
+}
+
+/// p50, p95 and p99 latencies of each endpoint, keyed by method and route. Only available with
+/// `metrics.latency_percentiles`.
+#[get("/stats/latency")]
+fn latency_stats(
+    options: State<Options>,
+    metrics: State<Metrics>,
+) -> Option<Json<BTreeMap<String, LatencySummary>>> {
+    if !options.latency_percentiles {
+        return None;
+    }
+    Some(Json(metrics.latencies()))
+}
+
+/// /health response, the status of each external service.
+#[derive(Debug, Serialize)]
+pub struct Health {
+    pub pokeapi: &'static str,
+    pub funtranslations: &'static str,
+}
+
+/// Pings both external services, responding with 503 if either is down.
+#[get("/health")]
+fn health(
+    pokeapi: State<BoxedPokeApi>,
+    translator: State<BoxedTranslator>,
+) -> status::Custom<Json<Health>> {
+    fn check(service: &str, result: anyhow::Result<()>) -> &'static str {
+        match result {
+            Ok(()) => "ok",
+            Err(e) => {
+                warn!("{} health check failed: {:#}", service, e);
+                "down"
+            }
+        }
+    }
+
+    let health = Health {
+        pokeapi: check("PokeAPI", pokeapi.ping()),
+        funtranslations: check("Fun Translations", translator.ping()),
+    };
+    let status = if health.pokeapi == "ok" && health.funtranslations == "ok" {
+        Status::Ok
+    } else {
+        Status::ServiceUnavailable
+    };
+    status::Custom(status, Json(health))
+}
+
+/// Liveness reported by `/healthz`, which can be failed on purpose to test orchestration.
+#[derive(Debug, Default)]
+struct Liveness {
+    failing: AtomicBool,
+}
+
+/// Liveness probe, responds with 503 while failing was injected with `POST /debug/fail-health`.
+#[get("/healthz")]
+fn healthz(liveness: State<Liveness>) -> status::Custom<Json<serde_json::Value>> {
+    if liveness.failing.load(Ordering::SeqCst) {
+        status::Custom(
+            Status::ServiceUnavailable,
+            Json(json!({ "status": "failing" })),
+        )
+    } else {
+        status::Custom(Status::Ok, Json(json!({ "status": "ok" })))
+    }
+}
+
+/// Toggles `/healthz` between failing and healthy, responding with the new state. Only available
+/// with `debug.enabled`.
+#[post("/debug/fail-health")]
+fn fail_health(
+    _admin: Admin,
+    options: State<Options>,
+    liveness: State<Liveness>,
+) -> ApiResult<serde_json::Value> {
+    if !options.debug {
+        return Err(ApiError::Status(Status::NotFound));
+    }
+    let failing = !liveness.failing.fetch_xor(true, Ordering::SeqCst);
+    warn!(
+        "Liveness probe is now {}",
+        if failing { "failing" } else { "ok" }
+    );
+    Ok(Json(json!({ "failing": failing })))
+}
+
+/// Size and hit rate of the description cache.
+#[get("/cache/stats")]
+fn cache_stats(cache: State<Cache>) -> Json<CacheStats> {
+    Json(cache.stats())
+}
+
+/// Cached keys from the most to the least recently used. Only available with `debug.enabled`.
+#[get("/debug/cache/order")]
+fn cache_order(options: State<Options>, cache: State<Cache>) -> ApiResult<Vec<CacheKey>> {
+    if !options.debug {
+        return Err(ApiError::Status(Status::NotFound));
+    }
+    Ok(Json(cache.order()))
+}
+
+/// Recent cache insertions, evictions and removals, oldest first. Only available with
+/// `cache.audit_log_size`.
+#[get("/cache/log")]
+fn cache_log(_admin: Admin, cache: State<Cache>) -> Option<Json<Vec<CacheLogEntry>>> {
+    cache.audit_log().map(Json)
+}
+
+/// Purges the cached translations of a pokemon, responding with 404 if there were none.
+#[delete("/cache/<name>")]
+fn purge_cache(_admin: Admin, name: Alpha, cache: State<Cache>) -> Result<Status, ApiError> {
+    if cache.remove(&Species::Name(name.canonical())) {
+        Ok(Status::NoContent)
+    } else {
+        Err(ApiError::Status(Status::NotFound))
     }
 }
 
-#[get("/pokemon/<_name>", rank = 2)]
-fn pokemon_badrequest(_name: &RawStr) -> status::BadRequest<()> {
-    status::BadRequest(None)
+/// Effective configuration, with secrets redacted. Services are only reported when instantiated
+/// from the configuration.
+#[get("/config")]
+fn effective_config(
+    _admin: Admin,
+    options: State<Options>,
+    services: Option<State<ServicesConfig>>,
+) -> Json<serde_json::Value> {
+    Json(json!({
+        "options": options.inner(),
+        "services": services.map(|s| s.inner().clone()),
+    }))
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
-    use rocket::config::{Config, Environment};
-    use rocket::http::ContentType;
-    use rocket::local::Client;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+    use std::time::{Duration, SystemTime};
+
+    use anyhow::anyhow;
+    use rocket::config::{Config, Environment, Value};
+    use rocket::http::{ContentType, Header};
+    use rocket::local::{Client, LocalResponse};
     use serde::de::DeserializeOwned;
 
+    use crate::api::InflightLimiter;
+    use crate::services::{Lookup, Style};
+    use crate::test_utils::MockServer;
+
     #[test]
     fn test_pokemon_ok() {
         let rocket = rocket::custom(Config::new(Environment::Development)).poke_shakespeare_custom(
             |name: &str| match name {
-                "foo" => Ok(Some("desc foo".to_string())),
-                "bar" => Ok(Some("my name is bar".to_string())),
-                _ => Ok(None),
+                "foo" => Ok(Lookup::Found("desc foo".to_string())),
+                "bar" => Ok(Lookup::Found("my name is bar".to_string())),
+                _ => Ok(Lookup::Missing),
             },
             |source: &str| Ok(format!("TRANSLATED: {}", source)),
         );
@@ -118,6 +863,7 @@ mod test {
                 Pokemon {
                     name: "foo".into(),
                     description: "TRANSLATED: desc foo".into(),
+                    ..Default::default()
                 }
             ),
             json_get(&client, "/pokemon/foo"),
@@ -129,6 +875,7 @@ mod test {
                 Pokemon {
                     name: "bar".into(),
                     description: "TRANSLATED: my name is bar".into(),
+                    ..Default::default()
                 }
             ),
             json_get(&client, "/pokemon/bar"),
@@ -138,14 +885,1989 @@ mod test {
     #[test]
     fn test_invalid_param_responds_bad_request() {
         let rocket = rocket::custom(Config::new(Environment::Development))
-            .poke_shakespeare_custom(|_: &str| Ok(None), |s: &str| Ok(s.to_string()));
+            .poke_shakespeare_custom(|_: &str| Ok(Lookup::Missing), |s: &str| Ok(s.to_string()));
         let client = Client::new(rocket).unwrap();
-        let response = client.get("/pokemon/12").dispatch();
+        let response = client.get("/pokemon/p1kachu").dispatch();
         assert_eq!(response.status(), Status::BadRequest);
         let response = client.get("/pokemon/foo&20bar").dispatch();
         assert_eq!(response.status(), Status::BadRequest);
     }
 
+    #[test]
+    fn test_failed_translation_dead_letter() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dead_letters.jsonl");
+        let mut funtranslations = HashMap::new();
+        funtranslations.insert("dead_letter_file", Value::from(path.to_str().unwrap()));
+        let config = Config::build(Environment::Development)
+            .extra("funtranslations", funtranslations)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |_: &str| Ok(Lookup::Found("desc foo".to_string())),
+            |_: &str| Err(anyhow!("Too Many Requests")),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let response = client.get("/pokemon/foo").dispatch();
+        assert_eq!(response.status(), Status::InternalServerError);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![serde_json::json!({
+                "name": "foo",
+                "source": "desc foo",
+                "error": "Too Many Requests",
+            })],
+        );
+    }
+
+    #[test]
+    fn test_load_shedding() {
+        let mut loadshed = HashMap::new();
+        loadshed.insert("miss_rate_threshold", Value::from(0.5));
+        loadshed.insert("min_requests", Value::from(4));
+        let config = Config::build(Environment::Development)
+            .extra("loadshed", loadshed)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |name: &str| Ok(Lookup::Found(format!("desc {}", name))),
+            |source: &str| Ok(source.to_string()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        for endpoint in &["/pokemon/a", "/pokemon/b", "/pokemon/c", "/pokemon/d"] {
+            assert_eq!(client.get(*endpoint).dispatch().status(), Status::Ok);
+        }
+        let response = client.get("/pokemon/e").dispatch();
+        assert_eq!(response.status(), Status::ServiceUnavailable);
+        assert_eq!(
+            (
+                Status::Ok,
+                Pokemon {
+                    name: "d".into(),
+                    description: "desc d".into(),
+                    ..Default::default()
+                }
+            ),
+            json_get(&client, "/pokemon/d"),
+        );
+
+        // Shedding subsides once hits bring the miss rate down, however many misses are rejected
+        for _ in 0..3 {
+            let response = client.get("/pokemon/e").dispatch();
+            assert_eq!(response.status(), Status::ServiceUnavailable);
+            assert_eq!(client.get("/pokemon/d").dispatch().status(), Status::Ok);
+        }
+        assert_eq!(client.get("/pokemon/e").dispatch().status(), Status::Ok);
+    }
+
+    #[test]
+    fn test_generated_at() {
+        let config = Config::build(Environment::Development)
+            .extra("include_timestamp", true)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |_: &str| Ok(Lookup::Found("desc".into())),
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let before = SystemTime::now() - Duration::from_millis(1);
+        let (status, first): (_, Pokemon) = json_get(&client, "/pokemon/foo");
+        let after = SystemTime::now();
+        assert_eq!(status, Status::Ok);
+        let generated_at = humantime::parse_rfc3339(first.generated_at.as_ref().unwrap()).unwrap();
+        assert!(before <= generated_at && generated_at <= after);
+
+        std::thread::sleep(Duration::from_millis(10));
+        let (_, second): (_, Pokemon) = json_get(&client, "/pokemon/foo");
+        assert_eq!(first.generated_at, second.generated_at);
+    }
+
+    #[test]
+    fn test_include_names() {
+        let config = Config::build(Environment::Development)
+            .extra("include_names", true)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |name: &str| match name {
+                "pikachu" => Ok(Lookup::Found("desc pikachu".to_string())),
+                _ => Ok(Lookup::Missing),
+            },
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        assert_eq!(
+            (
+                Status::Ok,
+                Pokemon {
+                    name: "pikachu".into(),
+                    description: "desc pikachu".into(),
+                    requested_name: Some("Pikachu".into()),
+                    canonical_name: Some("pikachu".into()),
+                    ..Default::default()
+                }
+            ),
+            json_get(&client, "/pokemon/Pikachu"),
+        );
+    }
+
+    #[test]
+    fn test_empty_description() {
+        let pokeapi = |_: &str| Ok(Lookup::Found(" \n".to_string()));
+        let translator = |_: &str| Err(anyhow!("Translator must not be called"));
+
+        let rocket = rocket::custom(Config::new(Environment::Development))
+            .poke_shakespeare_custom(pokeapi, translator);
+        let client = Client::new(rocket).unwrap();
+        let response = client.get("/pokemon/foo").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+
+        let config = Config::build(Environment::Development)
+            .extra("empty_description", "Nothing is known of this one")
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(pokeapi, translator);
+        let client = Client::new(rocket).unwrap();
+        let (status, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/foo");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(pokemon.description, "Nothing is known of this one");
+    }
+
+    #[test]
+    fn test_max_repeated_chars() {
+        let config = Config::build(Environment::Development)
+            .extra("max_repeated_chars", 3)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |name: &str| Ok(Lookup::Found(format!("desc {}", name))),
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let response = client.get("/pokemon/aaaaaaaaaaaaaaaaaaaa").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        for name in &["bulbasaur", "goodra", "Hoothoot"] {
+            let response = client.get(format!("/pokemon/{}", name)).dispatch();
+            assert_eq!(response.status(), Status::Ok);
+        }
+    }
+
+    #[test]
+    fn test_debug_translation() {
+        struct DebugTranslator;
+
+        impl Translator for DebugTranslator {
+            fn translate(&self, source: &str) -> anyhow::Result<String> {
+                Ok(source.into())
+            }
+
+            fn translate_debug(
+                &self,
+                source: &str,
+            ) -> anyhow::Result<(String, Option<serde_json::Value>)> {
+                Ok((source.into(), Some(serde_json::json!({ "raw": source }))))
+            }
+        }
+
+        let make_client = |debug_enabled: bool| {
+            let mut debug = HashMap::new();
+            debug.insert("enabled", debug_enabled);
+            let config = Config::build(Environment::Development)
+                .extra("debug", debug)
+                .finalize()
+                .unwrap();
+            let rocket = rocket::custom(config).poke_shakespeare_custom(
+                |_: &str| Ok(Lookup::Found("desc".into())),
+                DebugTranslator,
+            );
+            Client::new(rocket).unwrap()
+        };
+
+        let client = make_client(true);
+        let (_, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/foo?debug_translation=true");
+        assert_eq!(pokemon.debug, Some(serde_json::json!({ "raw": "desc" })));
+        let (_, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/foo");
+        assert_eq!(pokemon.debug, None);
+
+        let client = make_client(false);
+        let (_, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/foo?debug_translation=true");
+        assert_eq!(pokemon.debug, None);
+    }
+
+    #[test]
+    fn test_max_inflight_requests() {
+        let mut http = HashMap::new();
+        http.insert("max_inflight_requests", 1);
+        let config = Config::build(Environment::Development)
+            .extra("http", http)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |_: &str| Ok(Lookup::Found("desc".into())),
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+        let limiter = client.rocket().state::<InflightLimiter>().unwrap();
+
+        let permit = limiter.try_acquire().unwrap();
+        let response = client.get("/pokemon/foo").dispatch();
+        assert_eq!(response.status(), Status::ServiceUnavailable);
+        drop(permit);
+        let response = client.get("/pokemon/foo").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[test]
+    fn test_description_chunks() {
+        let rocket = rocket::custom(Config::new(Environment::Development)).poke_shakespeare_custom(
+            |_: &str| Ok(Lookup::Found("Thou art a pokémon".into())),
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let mut chunks = Vec::new();
+        let mut next = Some(0);
+        while let Some(chunk) = next {
+            let (status, pokemon): (_, Pokemon) = json_get(
+                &client,
+                &format!("/pokemon/foo?chunk={}&chunk_size=8", chunk),
+            );
+            assert_eq!(status, Status::Ok);
+            chunks.push(pokemon.description);
+            next = pokemon.next_chunk;
+        }
+        assert_eq!(chunks, vec!["Thou art", " a pokém", "on"]);
+
+        let response = client.get("/pokemon/foo?chunk=3&chunk_size=8").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        let (_, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/foo");
+        assert_eq!(pokemon.description, "Thou art a pokémon");
+        assert_eq!(pokemon.next_chunk, None);
+    }
+
+    #[test]
+    fn test_fail_health() {
+        let make_client = |debug_enabled: bool| {
+            let mut admin = HashMap::new();
+            admin.insert("token", Value::from("hunter2"));
+            let mut debug = HashMap::new();
+            debug.insert("enabled", Value::from(debug_enabled));
+            let config = Config::build(Environment::Development)
+                .extra("admin", admin)
+                .extra("debug", debug)
+                .finalize()
+                .unwrap();
+            let rocket = rocket::custom(config)
+                .poke_shakespeare_custom(|_: &str| Ok(Lookup::Missing), |s: &str| Ok(s.into()));
+            Client::new(rocket).unwrap()
+        };
+        let toggle = |client: &Client| {
+            client
+                .post("/debug/fail-health")
+                .header(Header::new("Authorization", "Bearer hunter2"))
+                .dispatch()
+                .status()
+        };
+
+        let client = make_client(false);
+        assert_eq!(toggle(&client), Status::NotFound);
+        assert_eq!(client.get("/healthz").dispatch().status(), Status::Ok);
+
+        let client = make_client(true);
+        let response = client.post("/debug/fail-health").dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        assert_eq!(client.get("/healthz").dispatch().status(), Status::Ok);
+        assert_eq!(toggle(&client), Status::Ok);
+        assert_eq!(
+            client.get("/healthz").dispatch().status(),
+            Status::ServiceUnavailable
+        );
+        assert_eq!(toggle(&client), Status::Ok);
+        assert_eq!(client.get("/healthz").dispatch().status(), Status::Ok);
+    }
+
+    #[test]
+    fn test_rate_limit_per_key() {
+        let mut ratelimit = HashMap::new();
+        ratelimit.insert("per_key_per_hour", Value::from(2));
+        ratelimit.insert("keys", Value::from(vec!["alice", "bob"]));
+        let config = Config::build(Environment::Development)
+            .extra("ratelimit", ratelimit)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |_: &str| Ok(Lookup::Found("desc".into())),
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+        let get = |key: Option<&str>, ip: [u8; 4]| {
+            let mut request = client.get("/pokemon/pikachu").remote((ip, 8000).into());
+            if let Some(key) = key {
+                request.add_header(Header::new("Authorization", format!("Bearer {}", key)));
+            }
+            request.dispatch()
+        };
+
+        assert_eq!(get(Some("alice"), [10, 0, 0, 1]).status(), Status::Ok);
+        assert_eq!(get(Some("alice"), [10, 0, 0, 2]).status(), Status::Ok);
+        let response = get(Some("alice"), [10, 0, 0, 1]);
+        assert_eq!(response.status(), Status::TooManyRequests);
+        let retry_after: u64 = response
+            .headers()
+            .get_one("Retry-After")
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(retry_after > 0 && retry_after <= 60 * 60);
+
+        assert_eq!(get(Some("bob"), [10, 0, 0, 1]).status(), Status::Ok);
+        assert_eq!(get(Some("bob"), [10, 0, 0, 1]).status(), Status::Ok);
+        assert_eq!(
+            get(Some("bob"), [10, 0, 0, 1]).status(),
+            Status::TooManyRequests
+        );
+
+        // Unknown and absent keys share the limit of the client IP
+        assert_eq!(get(Some("mallory"), [10, 0, 0, 1]).status(), Status::Ok);
+        assert_eq!(get(None, [10, 0, 0, 1]).status(), Status::Ok);
+        assert_eq!(get(None, [10, 0, 0, 1]).status(), Status::TooManyRequests);
+        assert_eq!(get(None, [10, 0, 0, 2]).status(), Status::Ok);
+
+        // Spoofing the IP doesn't reset the limit
+        for ip in &["192.0.2.1", "192.0.2.2"] {
+            let response = client
+                .get("/pokemon/pikachu")
+                .remote(([10, 0, 0, 1], 8000).into())
+                .header(Header::new("X-Real-IP", *ip))
+                .dispatch();
+            assert_eq!(response.status(), Status::TooManyRequests);
+        }
+    }
+
+    #[test]
+    fn test_rate_limit_batch() {
+        let mut ratelimit = HashMap::new();
+        ratelimit.insert("per_key_per_hour", Value::from(3));
+        let config = Config::build(Environment::Development)
+            .extra("ratelimit", ratelimit)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |_: &str| Ok(Lookup::Found("desc".into())),
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+        let batch = |names: &str| {
+            client
+                .post("/pokemon")
+                .header(ContentType::JSON)
+                .remote(([10, 0, 0, 1], 8000).into())
+                .body(names)
+                .dispatch()
+                .status()
+        };
+
+        // Every distinct name counts against the limit
+        assert_eq!(
+            batch(r#"["pikachu", "eevee", "ditto", "mew"]"#),
+            Status::TooManyRequests
+        );
+        assert_eq!(batch(r#"["pikachu", "eevee", "pikachu"]"#), Status::Ok);
+        assert_eq!(batch(r#"["ditto", "mew"]"#), Status::TooManyRequests);
+        assert_eq!(batch(r#"["ditto"]"#), Status::Ok);
+        assert_eq!(batch(r#"["mew"]"#), Status::TooManyRequests);
+    }
+
+    #[test]
+    fn test_not_found_kinds() {
+        let client = Client::new(rocket::ignite().poke_shakespeare_custom(
+            |name: &str| match name {
+                "pikachu" => Ok(Lookup::Found("desc".into())),
+                "eevee" => Ok(Lookup::NoDescription),
+                _ => Ok(Lookup::Missing),
+            },
+            |s: &str| Ok(s.into()),
+        ))
+        .unwrap();
+
+        let (status, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/pikachu");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(pokemon.description, "desc");
+        // Twice to also check cached entries
+        for _ in 0..2 {
+            let (status, body): (_, serde_json::Value) = json_get(&client, "/pokemon/eevee");
+            assert_eq!(status, Status::NotFound);
+            assert_eq!(body["kind"], "no_english_description");
+            let (status, body): (_, serde_json::Value) = json_get(&client, "/pokemon/missingno");
+            assert_eq!(status, Status::NotFound);
+            assert_eq!(body["kind"], "pokemon_not_found");
+        }
+    }
+
+    #[test]
+    fn test_not_found_suggestions() {
+        struct MockPokeApi(Arc<AtomicUsize>);
+
+        impl PokeApi for MockPokeApi {
+            fn get_description(&self, name: &str) -> anyhow::Result<Lookup> {
+                match name {
+                    "charizard" | "charmander" => Ok(Lookup::Found("desc".into())),
+                    _ => Ok(Lookup::Missing),
+                }
+            }
+
+            fn list_names(&self) -> anyhow::Result<Vec<String>> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(vec!["bulbasaur", "charmander", "charizard", "pikachu"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect())
+            }
+        }
+
+        let listings = Arc::new(AtomicUsize::new(0));
+        let client = Client::new(
+            rocket::ignite()
+                .poke_shakespeare_custom(MockPokeApi(listings.clone()), |s: &str| Ok(s.into())),
+        )
+        .unwrap();
+
+        let (status, _): (_, Pokemon) = json_get(&client, "/pokemon/Charizard");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(listings.load(Ordering::SeqCst), 0);
+
+        let (status, body): (_, serde_json::Value) = json_get(&client, "/pokemon/charzard");
+        assert_eq!(status, Status::NotFound);
+        assert_eq!(
+            body,
+            json!({
+                "error": "Not Found",
+                "code": 404,
+                "kind": "pokemon_not_found",
+                "suggestions": ["charizard", "charmander"],
+            })
+        );
+        // Names are only listed once
+        let (_, body): (_, serde_json::Value) = json_get(&client, "/pokemon/pikachoo");
+        assert_eq!(body["suggestions"], json!(["pikachu"]));
+        assert_eq!(listings.load(Ordering::SeqCst), 1);
+        let (_, body): (_, serde_json::Value) = json_get(&client, "/pokemon/zzzzzz");
+        assert!(body.get("suggestions").is_none());
+    }
+
+    #[test]
+    fn test_persisted_cache_config_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.json");
+        let make_client = |mock_format: &str| {
+            let mut pokeapi = HashMap::new();
+            pokeapi.insert(
+                "mock",
+                Value::from(
+                    vec![("pikachu", "desc")]
+                        .into_iter()
+                        .collect::<HashMap<_, _>>(),
+                ),
+            );
+            let mut funtranslations = HashMap::new();
+            funtranslations.insert("mock", Value::from(true));
+            funtranslations.insert("mock_format", Value::from(mock_format));
+            let config = Config::build(Environment::Development)
+                .extra("cache_path", path.to_str().unwrap())
+                .extra("pokeapi", pokeapi)
+                .extra("funtranslations", funtranslations)
+                .finalize()
+                .unwrap();
+            Client::new(rocket::custom(config).poke_shakespeare()).unwrap()
+        };
+        let description = |client: &Client| {
+            let mut response = client.get("/pokemon/pikachu").dispatch();
+            let body: serde_json::Value =
+                serde_json::from_str(&response.body_string().unwrap()).unwrap();
+            body["description"].clone()
+        };
+
+        let client = make_client("OLD: {text}");
+        assert_eq!(description(&client), "OLD: desc");
+        drop(client);
+        assert!(path.exists());
+
+        // Translations cached with the old translator aren't served by the new one
+        let client = make_client("NEW: {text}");
+        assert_eq!(description(&client), "NEW: desc");
+    }
+
+    #[test]
+    fn test_cache_log() {
+        let make_client = |audit_log_size: Option<i64>| {
+            let mut admin = HashMap::new();
+            admin.insert("token", Value::from("hunter2"));
+            let mut cache = HashMap::new();
+            if let Some(size) = audit_log_size {
+                cache.insert("audit_log_size", Value::from(size));
+            }
+            let mut pokeapi = HashMap::new();
+            pokeapi.insert(
+                "mock",
+                Value::from(
+                    vec![("pikachu", "desc"), ("eevee", "desc")]
+                        .into_iter()
+                        .collect::<HashMap<_, _>>(),
+                ),
+            );
+            let mut funtranslations = HashMap::new();
+            funtranslations.insert("mock", true);
+            let config = Config::build(Environment::Development)
+                .extra("cache_size", 1)
+                .extra("cache", cache)
+                .extra("admin", admin)
+                .extra("pokeapi", pokeapi)
+                .extra("funtranslations", funtranslations)
+                .finalize()
+                .unwrap();
+            Client::new(rocket::custom(config).poke_shakespeare()).unwrap()
+        };
+        let log = |client: &Client| {
+            let mut response = client
+                .get("/cache/log")
+                .header(Header::new("Authorization", "Bearer hunter2"))
+                .dispatch();
+            let body = response.body_string();
+            (response.status(), body)
+        };
+
+        let client = make_client(None);
+        assert_eq!(log(&client).0, Status::NotFound);
+
+        let client = make_client(Some(10));
+        assert_eq!(
+            client.get("/cache/log").dispatch().status(),
+            Status::Unauthorized
+        );
+        client.get("/pokemon/pikachu").dispatch();
+        client.get("/pokemon/eevee").dispatch();
+        client
+            .delete("/cache/eevee")
+            .header(Header::new("Authorization", "Bearer hunter2"))
+            .dispatch();
+        let (status, body) = log(&client);
+        assert_eq!(status, Status::Ok);
+        let body: Vec<serde_json::Value> = serde_json::from_str(&body.unwrap()).unwrap();
+        let ops: Vec<_> = body
+            .iter()
+            .map(|entry| {
+                assert!(entry["at"].is_string());
+                (
+                    entry["op"].as_str().unwrap(),
+                    entry["key"]["species"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            ops,
+            vec![
+                ("insert", "pikachu"),
+                ("evict", "pikachu"),
+                ("insert", "eevee"),
+                ("remove", "eevee"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cache_stats() {
+        let client = Client::new(rocket::ignite().poke_shakespeare_custom(
+            |_: &str| Ok(Lookup::Found("desc".into())),
+            |s: &str| Ok(s.into()),
+        ))
+        .unwrap();
+        let stats = || {
+            let mut response = client.get("/cache/stats").dispatch();
+            assert_eq!(response.status(), Status::Ok);
+            serde_json::from_str::<serde_json::Value>(&response.body_string().unwrap()).unwrap()
+        };
+
+        assert_eq!(
+            stats(),
+            json!({"capacity": 1, "len": 0, "hits": 0, "misses": 0})
+        );
+        client.get("/pokemon/pikachu").dispatch();
+        client.get("/pokemon/pikachu").dispatch();
+        assert_eq!(
+            stats(),
+            json!({"capacity": 1, "len": 1, "hits": 1, "misses": 1})
+        );
+    }
+
+    #[test]
+    fn test_cache_order() {
+        let make_client = |debug_enabled: bool| {
+            let mut debug = HashMap::new();
+            debug.insert("enabled", debug_enabled);
+            let config = Config::build(Environment::Development)
+                .extra("debug", debug)
+                .finalize()
+                .unwrap();
+            let rocket = rocket::custom(config).poke_shakespeare_custom(
+                |_: &str| Ok(Lookup::Found("desc".into())),
+                |s: &str| Ok(s.into()),
+            );
+            Client::new(rocket).unwrap()
+        };
+
+        let client = make_client(false);
+        let response = client.get("/debug/cache/order").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+
+        let client = make_client(true);
+        // The custom cache only holds one entry, see `services::test` for longer orders
+        client.get("/pokemon/pikachu").dispatch();
+        client.get("/pokemon/eevee").dispatch();
+        let mut response = client.get("/debug/cache/order").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: serde_json::Value =
+            serde_json::from_str(&response.body_string().unwrap()).unwrap();
+        let species: Vec<_> = body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|key| key["species"].as_str().unwrap())
+            .collect();
+        assert_eq!(species, vec!["eevee"]);
+        assert_eq!(body[0]["style"], "shakespeare");
+    }
+
+    #[test]
+    fn test_purge_cache() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut admin = HashMap::new();
+        admin.insert("token", "hunter2");
+        let config = Config::build(Environment::Development)
+            .extra("admin", admin)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            {
+                let calls = calls.clone();
+                move |_: &str| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Ok(Lookup::Found("desc".into()))
+                }
+            },
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+        let purge = |name: &str| {
+            client
+                .delete(format!("/cache/{}", name))
+                .header(Header::new("Authorization", "Bearer hunter2"))
+                .dispatch()
+                .status()
+        };
+
+        client.get("/pokemon/pikachu").dispatch();
+        client.get("/pokemon/pikachu").dispatch();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        let response = client.delete("/cache/pikachu").dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        assert_eq!(purge("Pikachu"), Status::NoContent);
+        assert_eq!(purge("pikachu"), Status::NotFound);
+        assert_eq!(purge("eevee"), Status::NotFound);
+        client.get("/pokemon/pikachu").dispatch();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_effective_config() {
+        let mut admin = HashMap::new();
+        admin.insert("token", "hunter2");
+        let mut funtranslations = HashMap::new();
+        funtranslations.insert("api_key", "secret");
+        let mut pokeapi = HashMap::new();
+        pokeapi.insert("path_template", "species/{name}");
+        let config = Config::build(Environment::Development)
+            .extra("admin", admin)
+            .extra("funtranslations", funtranslations)
+            .extra("pokeapi", pokeapi)
+            .finalize()
+            .unwrap();
+        let client = Client::new(rocket::custom(config).poke_shakespeare()).unwrap();
+
+        let response = client.get("/config").dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        let response = client
+            .get("/config")
+            .header(Header::new("Authorization", "Bearer wrong"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        let mut response = client
+            .get("/config")
+            .header(Header::new("Authorization", "Bearer hunter2"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let config: serde_json::Value =
+            serde_json::from_slice(&response.body_bytes().unwrap()).unwrap();
+        assert_eq!(
+            config["services"],
+            json!({
+                "cache_size": 4096,
+                "cache_ttl_secs": null,
+                "cache_eviction": "lru",
+                "cache_validate_on_read": false,
+                "cache_audit_log_size": null,
+                "cache_path": null,
+                "cache_flush_interval_secs": null,
+                "request_timeout_secs": null,
+                "user_agent": concat!("poke_shakespeare/", env!("CARGO_PKG_VERSION")),
+                "pokeapi": {
+                    "url": "https://pokeapi.co/api/v2/pokemon-species/",
+                    "path_template": "species/{name}",
+                    "flavor_text_strategy": "first",
+                    "language": "en",
+                },
+                "funtranslations": {
+                    "mock": false,
+                    "url": "https://api.funtranslations.com/translate/shakespeare",
+                    "api_key": "<redacted>",
+                    "max_attempts": 3,
+                    "retry_base_delay_ms": 500,
+                    "max_retry_delay_ms": 10000,
+                },
+            })
+        );
+        assert_eq!(config["options"]["chunk_size"], json!(280));
+        assert!(!config.to_string().contains("secret"));
+        assert!(!config.to_string().contains("hunter2"));
+    }
+
+    #[test]
+    fn test_admin_disabled() {
+        let client = Client::new(
+            rocket::custom(Config::new(Environment::Development))
+                .poke_shakespeare_custom(|_: &str| Ok(Lookup::Missing), |s: &str| Ok(s.into())),
+        )
+        .unwrap();
+        let response = client
+            .get("/config")
+            .header(Header::new("Authorization", "Bearer "))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn test_known_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("names.txt");
+        std::fs::write(&path, "bulbasaur\nivysaur\n\nvenusaur\n").unwrap();
+        let config = Config::build(Environment::Development)
+            .extra("known_names_file", path.to_str().unwrap())
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |name: &str| {
+                assert_ne!(name, "notapokemon", "PokeAPI must not be called");
+                Ok(Lookup::Found(format!("desc {}", name)))
+            },
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let response = client.get("/pokemon/notapokemon").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        let (status, body): (_, serde_json::Value) = json_get(&client, "/pokemon/venusaurr");
+        assert_eq!(status, Status::NotFound);
+        assert_eq!(body["suggestions"], json!(["venusaur"]));
+        let response = client.get("/pokemon/Ivysaur").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[test]
+    fn test_first_sentence() {
+        let rocket = rocket::custom(Config::new(Environment::Development)).poke_shakespeare_custom(
+            |_: &str| {
+                Ok(Lookup::Found(
+                    "Mr. Mime is a pokémon. It mimes.\nIt's\nfound in Kanto.".into(),
+                ))
+            },
+            |source: &str| Ok(format!("TRANSLATED: {}", source)),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let (status, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/foo?first_sentence=true");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(pokemon.description, "TRANSLATED: Mr. Mime is a pokémon.");
+        let (_, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/foo");
+        assert_eq!(
+            pokemon.description,
+            "TRANSLATED: Mr. Mime is a pokémon. It mimes.\nIt's\nfound in Kanto."
+        );
+    }
+
+    #[test]
+    fn test_skip_if_archaic() {
+        let make_client = |skip_if_archaic: bool| {
+            let mut funtranslations = HashMap::new();
+            funtranslations.insert("skip_if_archaic", skip_if_archaic);
+            let config = Config::build(Environment::Development)
+                .extra("funtranslations", funtranslations)
+                .finalize()
+                .unwrap();
+            let rocket = rocket::custom(config).poke_shakespeare_custom(
+                |name: &str| match name {
+                    "archaic" => Ok(Lookup::Found("Thou shalt fear its flames.".into())),
+                    _ => Ok(Lookup::Found("It breathes fire.".into())),
+                },
+                |s: &str| Ok(format!("TRANSLATED: {}", s)),
+            );
+            Client::new(rocket).unwrap()
+        };
+
+        let client = make_client(true);
+        let (_, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/archaic");
+        assert_eq!(pokemon.description, "Thou shalt fear its flames.");
+        assert_eq!(pokemon.source, Some(DescriptionSource::RawArchaic));
+        let mut response = client.get("/pokemon/archaic").dispatch();
+        let body: serde_json::Value =
+            serde_json::from_slice(&response.body_bytes().unwrap()).unwrap();
+        assert_eq!(body["source"], json!("raw_archaic"));
+        let (_, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/modern");
+        assert_eq!(pokemon.description, "TRANSLATED: It breathes fire.");
+        assert_eq!(pokemon.source, None);
+
+        let client = make_client(false);
+        let (_, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/archaic");
+        assert_eq!(
+            pokemon.description,
+            "TRANSLATED: Thou shalt fear its flames."
+        );
+        assert_eq!(pokemon.source, None);
+    }
+
+    #[test]
+    fn test_min_chars_to_translate() {
+        let mut funtranslations = HashMap::new();
+        funtranslations.insert("min_chars_to_translate", 10);
+        let config = Config::build(Environment::Development)
+            .extra("funtranslations", funtranslations)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |name: &str| match name {
+                "short" => Ok(Lookup::Found("Tiny.".into())),
+                _ => Ok(Lookup::Found("It breathes fire.".into())),
+            },
+            |s: &str| Ok(format!("TRANSLATED: {}", s)),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let (_, body): (_, serde_json::Value) = json_get(&client, "/pokemon/short");
+        assert_eq!(body["description"], json!("Tiny."));
+        assert_eq!(body["source"], json!("raw_short"));
+        let (_, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/long");
+        assert_eq!(pokemon.description, "TRANSLATED: It breathes fire.");
+        assert_eq!(pokemon.source, None);
+    }
+
+    #[test]
+    fn test_static_descriptions() {
+        let mut static_descriptions = HashMap::new();
+        static_descriptions.insert("Pikachu", Value::from("Forsooth, 'tis a mouse"));
+        let mut pokeapi = HashMap::new();
+        pokeapi.insert("static_descriptions", Value::from(static_descriptions));
+        let config = Config::build(Environment::Development)
+            .extra("pokeapi", pokeapi)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |_: &str| Err(anyhow!("PokeAPI is down")),
+            |_: &str| Err(anyhow!("Fun Translations is down")),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let (status, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/pikachu");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(pokemon.description, "Forsooth, 'tis a mouse");
+        assert_eq!(pokemon.source, Some(DescriptionSource::Static));
+        let response = client.get("/pokemon/bulbasaur").dispatch();
+        assert_eq!(response.status(), Status::InternalServerError);
+    }
+
+    #[test]
+    fn test_strip_apostrophes() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let config = Config::build(Environment::Development)
+            .extra("strip_apostrophes", true)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            {
+                let calls = calls.clone();
+                move |name: &str| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Ok(Lookup::Found(format!("desc {}", name)))
+                }
+            },
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        for endpoint in &[
+            "/pokemon/Farfetch'd",
+            "/pokemon/Farfetch%E2%80%99d",
+            "/pokemon/farfetchd",
+        ] {
+            let (status, pokemon): (_, Pokemon) = json_get(&client, endpoint);
+            assert_eq!(status, Status::Ok);
+            assert_eq!(pokemon.name, "farfetchd");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_reading_time() {
+        let config = Config::build(Environment::Development)
+            .extra("include_reading_time", true)
+            .extra("reading_wpm", 60)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |name: &str| match name {
+                "short" => Ok(Lookup::Found("Thou art small".into())),
+                _ => Ok(Lookup::Found("Thou art a most wondrous large beast".into())),
+            },
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let (_, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/short");
+        assert_eq!(pokemon.reading_time_seconds, Some(3));
+        let (_, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/long");
+        assert_eq!(pokemon.reading_time_seconds, Some(7));
+    }
+
+    #[test]
+    fn test_clean_output() {
+        let mut funtranslations = HashMap::new();
+        funtranslations.insert("clean_output", true);
+        let config = Config::build(Environment::Development)
+            .extra("funtranslations", funtranslations)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |_: &str| Ok(Lookup::Found("desc".into())),
+            |_: &str| Ok("Thou  art\n a   pokémon ".into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let (_, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/foo");
+        assert_eq!(pokemon.description, "Thou art a pokémon");
+    }
+
+    #[test]
+    fn test_title_case() {
+        let mut funtranslations = HashMap::new();
+        funtranslations.insert("title_case", true);
+        let config = Config::build(Environment::Development)
+            .extra("funtranslations", funtranslations)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |_: &str| Ok(Lookup::Found("desc".into())),
+            |_: &str| Ok("the mouse of the forest and its friend".into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let (_, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/foo");
+        assert_eq!(
+            pokemon.description,
+            "The Mouse of the Forest and Its Friend"
+        );
+    }
+
+    #[test]
+    fn test_health() {
+        let client =
+            Client::new(rocket::ignite().poke_shakespeare_custom(
+                |_: &str| Ok(Lookup::Missing),
+                |s: &str| Ok(s.to_string()),
+            ))
+            .unwrap();
+        let (status, health): (_, serde_json::Value) = json_get(&client, "/health");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(health, json!({"pokeapi": "ok", "funtranslations": "ok"}));
+
+        let client = Client::new(rocket::ignite().poke_shakespeare_custom(
+            |_: &str| Ok(Lookup::Missing),
+            |_: &str| Err(anyhow::anyhow!("Fun Translations is down")),
+        ))
+        .unwrap();
+        let (status, health): (_, serde_json::Value) = json_get(&client, "/health");
+        assert_eq!(status, Status::ServiceUnavailable);
+        assert_eq!(health, json!({"pokeapi": "ok", "funtranslations": "down"}));
+    }
+
+    #[test]
+    fn test_options_method() {
+        let make_client = |enabled: bool| {
+            let mut http = HashMap::new();
+            http.insert("options_method", enabled);
+            let config = Config::build(Environment::Development)
+                .extra("http", http)
+                .finalize()
+                .unwrap();
+            let rocket = rocket::custom(config).poke_shakespeare_custom(
+                |_: &str| Ok(Lookup::Found("desc".into())),
+                |s: &str| Ok(s.into()),
+            );
+            Client::new(rocket).unwrap()
+        };
+
+        let client = make_client(false);
+        let response = client.options("/pokemon/pikachu").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+
+        let client = make_client(true);
+        let response = client.options("/pokemon/pikachu").dispatch();
+        assert_eq!(response.status(), Status::NoContent);
+        assert_eq!(
+            response.headers().get_one("Allow"),
+            Some("GET, HEAD, OPTIONS")
+        );
+    }
+
+    #[test]
+    fn test_metrics() {
+        let make_client = |enabled: bool| {
+            let mut metrics = HashMap::new();
+            metrics.insert("enabled", enabled);
+            let config = Config::build(Environment::Development)
+                .extra("metrics", metrics)
+                .finalize()
+                .unwrap();
+            let rocket = rocket::custom(config).poke_shakespeare_custom(
+                |_: &str| Ok(Lookup::Found("desc".into())),
+                |s: &str| Ok(s.into()),
+            );
+            Client::new(rocket).unwrap()
+        };
+
+        let client = make_client(false);
+        assert_eq!(client.get("/metrics").dispatch().status(), Status::NotFound);
+
+        let client = make_client(true);
+        client.get("/pokemon/foo").dispatch();
+        client.get("/pokemon/bar").dispatch();
+        let mut response = client.get("/metrics").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body = response.body_string().unwrap();
+        assert!(body.contains("cache_evictions_total 1\n"));
+        assert!(body.contains("cache_expirations_total 0\n"));
+        assert!(body.contains("cache_hits_total 0\n"));
+        assert!(body.contains("cache_misses_total 2\n"));
+        assert!(body.contains("pokemon_requests_total{outcome=\"miss\"} 2\n"));
+
+        client.get("/pokemon/bar").dispatch();
+        client.get("/pokemon/p1kachu").dispatch();
+        let body = client.get("/metrics").dispatch().body_string().unwrap();
+        assert!(body.contains("pokemon_requests_total{outcome=\"hit\"} 1\n"));
+        assert!(body.contains("pokemon_requests_total{outcome=\"error\"} 1\n"));
+        assert!(body.contains("upstream_request_duration_seconds_count{service=\"pokeapi\"} 2\n"));
+    }
+
+    #[test]
+    fn test_latency_stats() {
+        let make_client = |enabled: bool| {
+            let mut metrics = HashMap::new();
+            metrics.insert("latency_percentiles", enabled);
+            let config = Config::build(Environment::Development)
+                .extra("metrics", metrics)
+                .finalize()
+                .unwrap();
+            let rocket = rocket::custom(config).poke_shakespeare_custom(
+                |_: &str| Ok(Lookup::Found("desc".into())),
+                |s: &str| Ok(s.into()),
+            );
+            Client::new(rocket).unwrap()
+        };
+
+        let client = make_client(false);
+        client.get("/pokemon/foo").dispatch();
+        assert_eq!(
+            client.get("/stats/latency").dispatch().status(),
+            Status::NotFound
+        );
+
+        let client = make_client(true);
+        client.get("/pokemon/foo").dispatch();
+        client.get("/pokemon/bar").dispatch();
+        client.get("/health").dispatch();
+        let (status, body): (_, serde_json::Value) = json_get(&client, "/stats/latency");
+        assert_eq!(status, Status::Ok);
+        let stats = body.as_object().unwrap();
+        assert_eq!(
+            stats.keys().collect::<Vec<_>>(),
+            ["GET /health", "GET /pokemon/<name>"]
+        );
+        assert_eq!(stats["GET /pokemon/<name>"]["count"], 2);
+        assert!(stats["GET /pokemon/<name>"]["p99_ms"].is_f64());
+    }
+
+    #[test]
+    fn test_root_key() {
+        let mut http = HashMap::new();
+        http.insert("root_key", "pokemon");
+        let config = Config::build(Environment::Development)
+            .extra("http", http)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |_: &str| Ok(Lookup::Found("desc".into())),
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let (status, body): (_, serde_json::Value) = json_get(&client, "/pokemon/foo");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(
+            body,
+            json!({"pokemon": {"name": "foo", "description": "desc"}})
+        );
+        let (status, body): (_, serde_json::Value) = json_get(&client, "/pokemon/p1kachu");
+        assert_eq!(status, Status::BadRequest);
+        assert_eq!(
+            body,
+            json!({"error": "Bad Request", "code": 400, "kind": "bad_request"})
+        );
+
+        let rocket = rocket::custom(Config::new(Environment::Development)).poke_shakespeare_custom(
+            |_: &str| Ok(Lookup::Found("desc".into())),
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+        let (_, body): (_, serde_json::Value) = json_get(&client, "/pokemon/foo");
+        assert_eq!(body, json!({"name": "foo", "description": "desc"}));
+    }
+
+    #[test]
+    fn test_etag() {
+        let client = Client::new(rocket::ignite().poke_shakespeare_custom(
+            |name: &str| Ok(Lookup::Found(format!("desc {}", name))),
+            |s: &str| Ok(s.into()),
+        ))
+        .unwrap();
+        let get = |path: &str, etag: Option<&str>| {
+            let mut request = client.get(path.to_string());
+            if let Some(etag) = etag {
+                request.add_header(Header::new("If-None-Match", etag.to_string()));
+            }
+            request.dispatch()
+        };
+
+        let mut response = get("/pokemon/pikachu", None);
+        assert_eq!(response.status(), Status::Ok);
+        let etag = response.headers().get_one("ETag").unwrap().to_string();
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
+        assert!(response.body_bytes().is_some());
+
+        let mut response = get("/pokemon/pikachu", Some(&etag));
+        assert_eq!(response.status(), Status::NotModified);
+        assert_eq!(response.headers().get_one("ETag"), Some(etag.as_str()));
+        assert_eq!(response.body_bytes(), None);
+
+        let response = get("/pokemon/pikachu", Some("\"stale\""));
+        assert_eq!(response.status(), Status::Ok);
+        let response = get("/pokemon/eevee", Some(&etag));
+        assert_eq!(response.status(), Status::Ok);
+        assert_ne!(response.headers().get_one("ETag"), Some(etag.as_str()));
+    }
+
+    #[test]
+    fn test_etag_style() {
+        struct StylelessTranslator;
+
+        impl Translator for StylelessTranslator {
+            fn translate(&self, source: &str) -> anyhow::Result<String> {
+                Ok(source.into())
+            }
+
+            fn translate_styled(
+                &self,
+                source: &str,
+                _: Style,
+            ) -> anyhow::Result<(String, Option<serde_json::Value>)> {
+                Ok((source.into(), None))
+            }
+        }
+
+        let client = Client::new(rocket::ignite().poke_shakespeare_custom(
+            |name: &str| Ok(Lookup::Found(format!("desc {}", name))),
+            StylelessTranslator,
+        ))
+        .unwrap();
+
+        let mut shakespeare = client.get("/pokemon/pikachu?style=shakespeare").dispatch();
+        let mut yoda = client.get("/pokemon/pikachu?style=yoda").dispatch();
+        assert_eq!(shakespeare.status(), Status::Ok);
+        assert_eq!(yoda.status(), Status::Ok);
+        assert_eq!(shakespeare.body_bytes(), yoda.body_bytes());
+        assert_ne!(
+            shakespeare.headers().get_one("ETag"),
+            yoda.headers().get_one("ETag")
+        );
+    }
+
+    #[test]
+    fn test_range_requests() {
+        let mut http = HashMap::new();
+        http.insert("range_requests", true);
+        http.insert("msgpack", true);
+        let config = Config::build(Environment::Development)
+            .extra("http", http)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |_: &str| Ok(Lookup::Found("desc".into())),
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let full = client.get("/pokemon/foo").dispatch().body_bytes().unwrap();
+        let mut response = client
+            .get("/pokemon/foo")
+            .header(Header::new("Range", "bytes=0-9"))
+            .dispatch();
+        assert_eq!(response.status(), Status::PartialContent);
+        assert_eq!(
+            response.headers().get_one("Content-Range"),
+            Some(format!("bytes 0-9/{}", full.len()).as_str())
+        );
+        assert_eq!(response.body_bytes().unwrap(), &full[..10]);
+
+        let response = client
+            .get("/pokemon/foo")
+            .header(Header::new("Range", "bytes=1000-"))
+            .header(Header::new("Accept", "application/msgpack"))
+            .dispatch();
+        assert_eq!(response.status(), Status::RangeNotSatisfiable);
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+        assert_eq!(response.headers().get_one("ETag"), None);
+    }
+
+    #[test]
+    fn test_cache_unknown_names() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let config = Config::build(Environment::Development)
+            .extra("cache_unknown_names", false)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            {
+                let calls = calls.clone();
+                move |name: &str| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    match name {
+                        "pikachu" => Ok(Lookup::Found("desc pikachu".into())),
+                        _ => Ok(Lookup::Missing),
+                    }
+                }
+            },
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        for _ in 0..2 {
+            let response = client.get("/pokemon/pikachu").dispatch();
+            assert_eq!(response.status(), Status::Ok);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        for _ in 0..2 {
+            let response = client.get("/pokemon/pikachuu").dispatch();
+            assert_eq!(response.status(), Status::NotFound);
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_translate() {
+        let make_client = |debug_enabled: bool| {
+            let mut debug = HashMap::new();
+            debug.insert("enabled", debug_enabled);
+            let config = Config::build(Environment::Development)
+                .extra("debug", debug)
+                .finalize()
+                .unwrap();
+            let rocket = rocket::custom(config).poke_shakespeare_custom(
+                |_: &str| Err(anyhow!("PokeAPI must not be called")),
+                |s: &str| Ok(format!("TRANSLATED: {}", s)),
+            );
+            Client::new(rocket).unwrap()
+        };
+        fn post(client: &Client, body: serde_json::Value) -> LocalResponse {
+            client
+                .post("/translate")
+                .header(ContentType::JSON)
+                .body(body.to_string())
+                .dispatch()
+        }
+
+        let client = make_client(true);
+        let mut response = post(&client, json!({ "text": "Hello there" }));
+        assert_eq!(response.status(), Status::Ok);
+        let translation: Translation =
+            serde_json::from_slice(&response.body_bytes().unwrap()).unwrap();
+        assert_eq!(translation.translation, "TRANSLATED: Hello there");
+        let response = post(&client, json!({ "text": "Hi", "style": "shakespeare" }));
+        assert_eq!(response.status(), Status::Ok);
+        let response = post(&client, json!({ "text": "Hi", "style": "klingon" }));
+        assert_eq!(response.status(), Status::BadRequest);
+
+        let client = make_client(false);
+        let response = post(&client, json!({ "text": "Hello there" }));
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn test_single_and_batch_lookups_coalesce() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let rocket = rocket::custom(Config::new(Environment::Development)).poke_shakespeare_custom(
+            {
+                let calls = calls.clone();
+                move |_: &str| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(200));
+                    Ok(Lookup::Found("desc".into()))
+                }
+            },
+            |s: &str| Ok(s.into()),
+        );
+        let client = Arc::new(Client::new(rocket).unwrap());
+        let barrier = Arc::new(Barrier::new(2));
+
+        let single = {
+            let (client, barrier) = (client.clone(), barrier.clone());
+            thread::spawn(move || {
+                barrier.wait();
+                let mut response = client.get("/pokemon/pikachu").dispatch();
+                (response.status(), response.body_string().unwrap())
+            })
+        };
+        let batch = thread::spawn(move || {
+            barrier.wait();
+            let mut response = client
+                .post("/pokemon")
+                .header(ContentType::JSON)
+                .body(r#"["Pikachu"]"#)
+                .dispatch();
+            (response.status(), response.body_string().unwrap())
+        });
+
+        let (status, body) = single.join().unwrap();
+        assert_eq!(status, Status::Ok);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!({"name": "pikachu", "description": "desc"})
+        );
+        let (status, body) = batch.join().unwrap();
+        assert_eq!(status, Status::Ok);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&body).unwrap(),
+            json!([{"name": "pikachu", "description": "desc"}])
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_pokemon_raw() {
+        let translations = Arc::new(AtomicUsize::new(0));
+        let rocket = rocket::custom(Config::new(Environment::Development)).poke_shakespeare_custom(
+            |name: &str| match name {
+                "pikachu" => Ok(Lookup::Found("It keeps\nits tail\u{c}raised.".into())),
+                _ => Ok(Lookup::Missing),
+            },
+            {
+                let translations = translations.clone();
+                move |s: &str| {
+                    translations.fetch_add(1, Ordering::SeqCst);
+                    Ok(format!("TRANSLATED: {}", s))
+                }
+            },
+        );
+        let client = Client::new(rocket).unwrap();
+
+        for _ in 0..2 {
+            let (status, body): (_, serde_json::Value) = json_get(&client, "/pokemon/Pikachu/raw");
+            assert_eq!(status, Status::Ok);
+            assert_eq!(
+                body,
+                json!({"name": "pikachu", "description": "It keeps its tail raised."})
+            );
+        }
+        assert_eq!(translations.load(Ordering::SeqCst), 0);
+
+        let mut raw = client.get("/pokemon/missingno/raw").dispatch();
+        let mut translated = client.get("/pokemon/missingno").dispatch();
+        assert_eq!(raw.status(), Status::NotFound);
+        assert_eq!(raw.body_string(), translated.body_string());
+        assert_eq!(translations.load(Ordering::SeqCst), 0);
+
+        // Raw descriptions don't stand in for translated ones
+        let (_, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/pikachu");
+        assert_eq!(
+            pokemon.description,
+            "TRANSLATED: It keeps\nits tail\u{c}raised."
+        );
+        assert_eq!(translations.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_pokemon_batch() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let rocket = rocket::custom(Config::new(Environment::Development)).poke_shakespeare_custom(
+            {
+                let calls = calls.clone();
+                move |name: &str| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    match name {
+                        "pikachu" | "bulbasaur" | "25" => {
+                            Ok(Lookup::Found(format!("desc {}", name)))
+                        }
+                        _ => Ok(Lookup::Missing),
+                    }
+                }
+            },
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let mut response = client
+            .post("/pokemon")
+            .header(ContentType::JSON)
+            .body(r#"["pikachu", "missingno", "Bulbasaur", "pikachu", "25"]"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: serde_json::Value =
+            serde_json::from_slice(&response.body_bytes().unwrap()).unwrap();
+        assert_eq!(
+            body,
+            json!([
+                {"name": "pikachu", "description": "desc pikachu"},
+                {"name": "missingno", "status": 404, "error": "Not Found"},
+                {"name": "bulbasaur", "description": "desc bulbasaur"},
+                {"name": "pikachu", "description": "desc pikachu"},
+                {"name": "25", "description": "desc 25"},
+            ])
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+        let mut response = client
+            .post("/pokemon")
+            .header(ContentType::JSON)
+            .body(r#"["pikachu", "p1kachu", "", "eevee", "-ditto"]"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        let body: serde_json::Value =
+            serde_json::from_slice(&response.body_bytes().unwrap()).unwrap();
+        let invalid: Vec<_> = body["invalid"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| {
+                assert!(entry["reason"].is_string());
+                (
+                    entry["index"].as_u64().unwrap(),
+                    entry["value"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(invalid, vec![(1, "p1kachu"), (2, ""), (4, "-ditto")]);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+        let names = serde_json::to_string(&vec!["pikachu"; MAX_BATCH_SIZE + 1]).unwrap();
+        let response = client
+            .post("/pokemon")
+            .header(ContentType::JSON)
+            .body(names)
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+
+        let response = client
+            .post("/pokemon")
+            .header(ContentType::JSON)
+            .body("[]")
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn test_pokemon_batch_allow_empty() {
+        let mut batch = HashMap::new();
+        batch.insert("allow_empty", true);
+        let config = Config::build(Environment::Development)
+            .extra("batch", batch)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |name: &str| Ok(Lookup::Found(format!("desc {}", name))),
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let mut response = client
+            .post("/pokemon")
+            .header(ContentType::JSON)
+            .body("[]")
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.body_string().unwrap(), "[]");
+    }
+
+    #[test]
+    fn test_pokemon_batch_normalizes_names() {
+        let config = Config::build(Environment::Development)
+            .extra("strip_apostrophes", true)
+            .extra("normalize_forms", true)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |name: &str| Ok(Lookup::Found(format!("desc {}", name))),
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let mut response = client
+            .post("/pokemon")
+            .header(ContentType::JSON)
+            .body(r#"["Farfetch'd", "Mega Charizard X"]"#)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body: serde_json::Value =
+            serde_json::from_slice(&response.body_bytes().unwrap()).unwrap();
+        assert_eq!(
+            body,
+            json!([
+                {"name": "farfetchd", "description": "desc farfetchd"},
+                {"name": "charizard-mega-x", "description": "desc charizard-mega-x"},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_translation_styles() {
+        struct StyledTranslator;
+
+        impl Translator for StyledTranslator {
+            fn translate(&self, source: &str) -> anyhow::Result<String> {
+                self.translate_styled(source, Style::Shakespeare)
+                    .map(|(t, _)| t)
+            }
+
+            fn translate_styled(
+                &self,
+                source: &str,
+                style: Style,
+            ) -> anyhow::Result<(String, Option<serde_json::Value>)> {
+                Ok((format!("{}: {}", style, source), None))
+            }
+        }
+
+        let make_client = |default_style: Option<&str>| {
+            let mut funtranslations = HashMap::new();
+            if let Some(style) = default_style {
+                funtranslations.insert("style", style);
+            }
+            let config = Config::build(Environment::Development)
+                .extra("funtranslations", funtranslations)
+                .finalize()
+                .unwrap();
+            let rocket = rocket::custom(config).poke_shakespeare_custom(
+                |_: &str| Ok(Lookup::Found("desc".into())),
+                StyledTranslator,
+            );
+            Client::new(rocket).unwrap()
+        };
+        let description = |client: &Client, endpoint: &str| {
+            let (status, pokemon): (_, Pokemon) = json_get(client, endpoint);
+            assert_eq!(status, Status::Ok);
+            pokemon.description
+        };
+
+        let client = make_client(None);
+        assert_eq!(description(&client, "/pokemon/foo"), "shakespeare: desc");
+        assert_eq!(
+            description(&client, "/pokemon/foo?style=yoda"),
+            "yoda: desc"
+        );
+        assert_eq!(description(&client, "/pokemon/foo"), "shakespeare: desc");
+        let response = client.get("/pokemon/foo?style=klingon").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+
+        let client = make_client(Some("pirate"));
+        assert_eq!(description(&client, "/pokemon/foo"), "pirate: desc");
+        assert_eq!(
+            description(&client, "/pokemon/foo?style=shakespeare"),
+            "shakespeare: desc"
+        );
+    }
+
+    #[test]
+    fn test_fallback_to_source() {
+        let make_client = |fallback: bool| {
+            let mut funtranslations = HashMap::new();
+            funtranslations.insert("fallback_to_source", fallback);
+            let config = Config::build(Environment::Development)
+                .extra("funtranslations", funtranslations)
+                .finalize()
+                .unwrap();
+            let rocket = rocket::custom(config).poke_shakespeare_custom(
+                |_: &str| Ok(Lookup::Found("A mouse.".into())),
+                |_: &str| Err(anyhow!("Fun Translations is down")),
+            );
+            Client::new(rocket).unwrap()
+        };
+
+        let client = make_client(false);
+        let response = client.get("/pokemon/pikachu").dispatch();
+        assert_eq!(response.status(), Status::InternalServerError);
+
+        let client = make_client(true);
+        for _ in 0..2 {
+            let (status, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/pikachu");
+            assert_eq!(status, Status::Ok);
+            assert_eq!(pokemon.description, "A mouse.");
+            assert_eq!(pokemon.translated, Some(false));
+            assert_eq!(pokemon.source, None);
+        }
+    }
+
+    #[test]
+    fn test_aliases() {
+        let mut aliases = HashMap::new();
+        aliases.insert("Pikachuu", "pikachu");
+        aliases.insert("pika", "Pikachu");
+        let config = Config::build(Environment::Development)
+            .extra("aliases", aliases)
+            .extra("include_aliases", true)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |name: &str| match name {
+                "pikachu" | "eevee" => Ok(Lookup::Found(format!("desc {}", name))),
+                _ => Ok(Lookup::Missing),
+            },
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        for endpoint in &["/pokemon/pikachu", "/pokemon/pika", "/pokemon/PIKACHUU"] {
+            let (status, pokemon): (_, Pokemon) = json_get(&client, endpoint);
+            assert_eq!(status, Status::Ok);
+            assert_eq!(pokemon.name, "pikachu");
+            assert_eq!(
+                pokemon.aliases,
+                Some(vec!["pika".to_string(), "pikachuu".to_string()])
+            );
+        }
+        let (_, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/eevee");
+        assert_eq!(pokemon.aliases, Some(vec![]));
+    }
+
+    #[test]
+    fn test_include_source() {
+        let rocket = rocket::custom(Config::new(Environment::Development)).poke_shakespeare_custom(
+            |_: &str| Ok(Lookup::Found("A mouse.".into())),
+            |s: &str| Ok(format!("TRANSLATED: {}", s)),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let (status, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/pikachu");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(pokemon.source_description, None);
+        let body = client
+            .get("/pokemon/pikachu")
+            .dispatch()
+            .body_string()
+            .unwrap();
+        assert!(!body.contains("source_description"));
+
+        let (status, pokemon): (_, Pokemon) =
+            json_get(&client, "/pokemon/pikachu?include_source=true");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(pokemon.description, "TRANSLATED: A mouse.");
+        assert_eq!(pokemon.source_description.as_deref(), Some("A mouse."));
+    }
+
+    #[test]
+    fn test_include_source_url() {
+        let server = MockServer::start(|_| {
+            (
+                200,
+                r#"{"flavor_text_entries":[{"flavor_text":"desc","language":{"name":"en"}}]}"#
+                    .into(),
+            )
+        });
+        let base_url = format!("{}/api/v2/pokemon-species/", server.url);
+        let mut pokeapi = HashMap::new();
+        pokeapi.insert("url", Value::from(base_url.as_str()));
+        let mut funtranslations = HashMap::new();
+        funtranslations.insert("mock", Value::from(true));
+        let config = Config::build(Environment::Development)
+            .extra("include_source_url", true)
+            .extra("pokeapi", pokeapi)
+            .extra("funtranslations", funtranslations)
+            .finalize()
+            .unwrap();
+        let client = Client::new(rocket::custom(config).poke_shakespeare()).unwrap();
+
+        let (status, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/Pikachu");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(pokemon.source_url, Some(format!("{}pikachu", base_url)));
+        assert_eq!(server.requests()[0].path, "/api/v2/pokemon-species/pikachu");
+    }
+
+    #[test]
+    fn test_upstream_rate_limit() {
+        let server = MockServer::start(|_| (429, r#"{"error":{"code":429}}"#.into()));
+        let mut pokeapi = HashMap::new();
+        pokeapi.insert(
+            "mock",
+            Value::from(
+                vec![("pikachu", "desc")]
+                    .into_iter()
+                    .collect::<HashMap<_, _>>(),
+            ),
+        );
+        let mut funtranslations = HashMap::new();
+        funtranslations.insert("url", Value::from(server.url.as_str()));
+        funtranslations.insert("max_attempts", Value::from(1));
+        funtranslations.insert("retry_base_delay_ms", Value::from(1500));
+        let config = Config::build(Environment::Development)
+            .extra("pokeapi", pokeapi)
+            .extra("funtranslations", funtranslations)
+            .finalize()
+            .unwrap();
+        let client = Client::new(rocket::custom(config).poke_shakespeare()).unwrap();
+
+        let mut response = client.get("/pokemon/pikachu").dispatch();
+        assert_eq!(response.status(), Status::ServiceUnavailable);
+        assert_eq!(response.headers().get_one("Retry-After"), Some("2"));
+        let body: serde_json::Value =
+            serde_json::from_str(&response.body_string().unwrap()).unwrap();
+        assert_eq!(
+            body,
+            json!({"error": "Service Unavailable", "code": 503, "kind": "rate_limited"})
+        );
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        let server = MockServer::start(|_| {
+            std::thread::sleep(std::time::Duration::from_secs(3));
+            (200, r#"{"flavor_text_entries":[]}"#.into())
+        });
+        let mut pokeapi = HashMap::new();
+        pokeapi.insert("url", Value::from(format!("{}/", server.url)));
+        let mut funtranslations = HashMap::new();
+        funtranslations.insert("mock", Value::from(true));
+        let config = Config::build(Environment::Development)
+            .extra("request_timeout_secs", 1)
+            .extra("pokeapi", pokeapi)
+            .extra("funtranslations", funtranslations)
+            .finalize()
+            .unwrap();
+        let client = Client::new(rocket::custom(config).poke_shakespeare()).unwrap();
+
+        let start = std::time::Instant::now();
+        let response = client.get("/pokemon/pikachu").dispatch();
+        assert_eq!(response.status(), Status::GatewayTimeout);
+        assert!(start.elapsed() < std::time::Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_user_agent() {
+        let server = MockServer::start(|request| {
+            if request.path.starts_with("/translate") {
+                (200, r#"{"contents":{"translated":"Thee mouse"}}"#.into())
+            } else {
+                (
+                    200,
+                    r#"{"flavor_text_entries":[{"flavor_text":"Mouse","language":{"name":"en"}}]}"#
+                        .into(),
+                )
+            }
+        });
+        let mut pokeapi = HashMap::new();
+        pokeapi.insert("url", format!("{}/species/", server.url));
+        let mut funtranslations = HashMap::new();
+        funtranslations.insert("url", format!("{}/translate/shakespeare", server.url));
+        let config = Config::build(Environment::Development)
+            .extra("user_agent", "pokedex-tests/1.0")
+            .extra("pokeapi", pokeapi)
+            .extra("funtranslations", funtranslations)
+            .finalize()
+            .unwrap();
+        let client = Client::new(rocket::custom(config).poke_shakespeare()).unwrap();
+
+        let (status, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/pikachu");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(pokemon.description, "Thee mouse");
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        for request in requests {
+            assert_eq!(request.header("User-Agent"), Some("pokedex-tests/1.0"));
+        }
+    }
+
+    #[test]
+    fn test_header_limits() {
+        let mut http = HashMap::new();
+        http.insert("max_header_bytes", 64);
+        http.insert("max_headers", 3);
+        let config = Config::build(Environment::Development)
+            .extra("http", http)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |_: &str| Ok(Lookup::Found("desc".into())),
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let response = client
+            .get("/pokemon/foo")
+            .header(Header::new("User-Agent", "curl/7.68.0"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let response = client
+            .get("/pokemon/foo")
+            .header(Header::new("User-Agent", "x".repeat(100)))
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        let mut request = client.get("/pokemon/foo");
+        for i in 0..4 {
+            request.add_header(Header::new(format!("X-Header-{}", i), "value"));
+        }
+        assert_eq!(request.dispatch().status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn test_numeric_id() {
+        let rocket = rocket::custom(Config::new(Environment::Development)).poke_shakespeare_custom(
+            |name: &str| match name {
+                "25" | "pikachu" => Ok(Lookup::Found(format!("desc {}", name))),
+                _ => Ok(Lookup::Missing),
+            },
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let (status, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/25");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(pokemon.name, "25");
+        assert_eq!(pokemon.description, "desc 25");
+        let (status, pokemon): (_, Pokemon) = json_get(&client, "/pokemon/pikachu");
+        assert_eq!(status, Status::Ok);
+        assert_eq!(pokemon.description, "desc pikachu");
+        let response = client.get("/pokemon/26").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn test_msgpack() {
+        let mut http = HashMap::new();
+        http.insert("msgpack", true);
+        let config = Config::build(Environment::Development)
+            .extra("http", http)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |_: &str| Ok(Lookup::Found("desc".into())),
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        let mut response = client
+            .get("/pokemon/foo")
+            .header(Header::new("Accept", "application/msgpack"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            response.content_type(),
+            Some(ContentType::new("application", "msgpack"))
+        );
+        assert_eq!(response.headers().get_one("Vary"), Some("Accept"));
+        let pokemon: Pokemon = rmp_serde::from_slice(&response.body_bytes().unwrap()).unwrap();
+        let (_, expected): (_, Pokemon) = json_get(&client, "/pokemon/foo");
+        assert_eq!(pokemon, expected);
+
+        let response = client.get("/pokemon/foo").dispatch();
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+        assert_eq!(response.headers().get_one("Vary"), Some("Accept"));
+    }
+
+    #[test]
+    fn test_normalize_forms() {
+        let config = Config::build(Environment::Development)
+            .extra("normalize_forms", true)
+            .finalize()
+            .unwrap();
+        let rocket = rocket::custom(config).poke_shakespeare_custom(
+            |name: &str| match name {
+                "charizard-mega-x" | "pikachu-gmax" => Ok(Lookup::Found(format!("desc {}", name))),
+                _ => Ok(Lookup::Missing),
+            },
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        for (endpoint, name) in &[
+            ("/pokemon/Mega%20Charizard%20X", "charizard-mega-x"),
+            ("/pokemon/Gigantamax%20Pikachu", "pikachu-gmax"),
+            ("/pokemon/pikachu-gmax", "pikachu-gmax"),
+        ] {
+            let (status, pokemon): (_, Pokemon) = json_get(&client, endpoint);
+            assert_eq!(status, Status::Ok);
+            assert_eq!(pokemon.name, *name);
+        }
+    }
+
+    #[test]
+    fn test_hyphenated_names() {
+        let rocket = rocket::custom(Config::new(Environment::Development)).poke_shakespeare_custom(
+            |name: &str| match name {
+                "mr-mime" | "ho-oh" | "nidoran-f" => Ok(Lookup::Found(format!("desc {}", name))),
+                _ => Ok(Lookup::Missing),
+            },
+            |s: &str| Ok(s.into()),
+        );
+        let client = Client::new(rocket).unwrap();
+
+        for (endpoint, name) in &[
+            ("/pokemon/mr-mime", "mr-mime"),
+            ("/pokemon/Ho-Oh", "ho-oh"),
+            ("/pokemon/Nidoran%E2%99%80", "nidoran-f"),
+        ] {
+            let (status, pokemon): (_, Pokemon) = json_get(&client, endpoint);
+            assert_eq!(status, Status::Ok);
+            assert_eq!(pokemon.name, *name);
+        }
+        let response = client.get("/pokemon/-mime").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
     #[test]
     #[ignore]
     fn test_api_integration() {
@@ -0,0 +1,321 @@
+//! Translation pipeline usable without serving it over HTTP, see `PokeShakespeare`.
+use anyhow::{anyhow, Result};
+use log::{error, warn};
+
+use crate::api::{Alpha, Species};
+use crate::config::Options;
+use crate::metrics::{Metrics, Upstream};
+use crate::services::{
+    BoxedPokeApi, BoxedTranslator, Cache, CacheEntry, CacheKey, DeadLetterLog, DescriptionSource,
+    Lookup, PokeApi, Style, Translator,
+};
+use crate::{text, Pokemon};
+
+/// Looks up and translates pokemon descriptions like `GET /pokemon/<name>` does, caching
+/// translations in the given `Cache`:
+///
+/// ```no_run
+/// use poke_shakespeare_lib::services::{Cache, FunTranslationsApi, PokeApiClient};
+/// use poke_shakespeare_lib::PokeShakespeare;
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let pipeline = PokeShakespeare::new(
+///     Box::new(PokeApiClient::default()),
+///     Box::new(FunTranslationsApi::default()),
+///     Cache::new(128),
+/// );
+/// let pokemon = pipeline.translate("Charizard")?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct PokeShakespeare {
+    pokeapi: BoxedPokeApi,
+    translator: BoxedTranslator,
+    cache: Cache,
+    options: Options,
+    dead_letters: DeadLetterLog,
+    metrics: Metrics,
+}
+
+impl PokeShakespeare {
+    /// Creates a pipeline with the default options, discarding failed translations.
+    pub fn new(pokeapi: BoxedPokeApi, translator: BoxedTranslator, cache: Cache) -> Self {
+        PokeShakespeare {
+            pokeapi,
+            translator,
+            cache,
+            options: Options::default(),
+            dead_letters: DeadLetterLog::disabled(),
+            metrics: Metrics::default(),
+        }
+    }
+
+    /// Translates descriptions as set in `options`, e.g. to another style or falling back to the
+    /// source description.
+    pub fn with_options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Records failed translations to `dead_letters`.
+    pub fn with_dead_letters(mut self, dead_letters: DeadLetterLog) -> Self {
+        self.dead_letters = dead_letters;
+        self
+    }
+
+    /// Returns the translated description of the pokemon `name`, or `None` if it doesn't exist or
+    /// has no description. Fails if `name` isn't a valid pokemon name or either service fails.
+    pub fn translate(&self, name: &str) -> Result<Option<Pokemon>> {
+        let species = match Species::parse_id(name) {
+            Some(id) => id,
+            None => Species::Name(
+                Alpha::try_new(name.to_string())
+                    .ok_or_else(|| anyhow!("Invalid pokemon name {:?}", name))?
+                    .canonical(),
+            ),
+        };
+        let name = species.to_string();
+        let key = CacheKey {
+            species,
+            first_sentence: false,
+            style: self.options.style,
+            raw: false,
+        };
+        let calculate = || {
+            calculate_entry(
+                &*self.pokeapi,
+                &*self.translator,
+                &self.dead_letters,
+                &self.metrics,
+                &self.options,
+                &key,
+            )
+            .map(|(entry, _)| entry)
+        };
+        let entry = self
+            .cache
+            .get_or_calculate_if(key.clone(), calculate, |entry| {
+                entry.source != DescriptionSource::Untranslated
+            })?;
+        Ok(entry.description.map(|description| Pokemon {
+            name,
+            description,
+            ..Default::default()
+        }))
+    }
+}
+
+/// Calculates the cache entry for `key`, fetching the species description and translating it as
+/// set in `options`. Also returns the raw translator response, if any.
+pub(crate) fn calculate_entry(
+    pokeapi: &dyn PokeApi,
+    translator: &dyn Translator,
+    dead_letters: &DeadLetterLog,
+    metrics: &Metrics,
+    options: &Options,
+    key: &CacheKey,
+) -> Result<(CacheEntry, Option<serde_json::Value>)> {
+    let name = key.species.to_string();
+    let source_description =
+        match metrics.time_upstream(Upstream::PokeApi, || pokeapi.get_description(&name))? {
+            Lookup::Found(source_description) if source_description.trim().is_empty() => {
+                let entry = CacheEntry {
+                    no_description: true,
+                    ..CacheEntry::new(options.empty_description.clone())
+                };
+                return Ok((entry, None));
+            }
+            Lookup::Found(source_description) => source_description,
+            Lookup::NoDescription => {
+                let entry = CacheEntry {
+                    no_description: true,
+                    ..CacheEntry::new(None)
+                };
+                return Ok((entry, None));
+            }
+            Lookup::Missing => return Ok((CacheEntry::new(None), None)),
+        };
+    let source = if key.first_sentence {
+        text::first_sentence(&source_description)
+    } else {
+        &source_description
+    };
+    let untranslated = |source_kind, description| CacheEntry {
+        source: source_kind,
+        source_description: Some(source.to_string()),
+        ..CacheEntry::new(Some(description))
+    };
+    if key.raw {
+        let entry = untranslated(DescriptionSource::Raw, text::collapse_whitespace(source));
+        return Ok((entry, None));
+    }
+    if options.skip_if_archaic && text::is_archaic(source) {
+        return Ok((
+            untranslated(DescriptionSource::RawArchaic, source.to_string()),
+            None,
+        ));
+    }
+    if source.chars().count() < options.min_chars_to_translate {
+        return Ok((
+            untranslated(DescriptionSource::RawShort, source.to_string()),
+            None,
+        ));
+    }
+    match metrics.time_upstream(Upstream::FunTranslations, || {
+        translator.translate_styled(source, key.style)
+    }) {
+        Ok((description, raw)) => {
+            let description = if options.clean_output {
+                text::collapse_whitespace(&description)
+            } else {
+                description
+            };
+            let description = if options.fix_yoda_output && key.style == Style::Yoda {
+                text::punctuate_sentence(&description)
+            } else {
+                description
+            };
+            let description = if options.title_case {
+                text::title_case(&description)
+            } else {
+                description
+            };
+            let entry = CacheEntry {
+                source_description: Some(source.to_string()),
+                ..CacheEntry::new(Some(description))
+            };
+            Ok((entry, raw))
+        }
+        Err(e) => {
+            if let Err(log_err) = dead_letters.record(&name, source, &e) {
+                error!("{}", log_err);
+            }
+            if !options.fallback_to_source {
+                return Err(e);
+            }
+            warn!("Serving untranslated description for {}: {:#}", name, e);
+            Ok((
+                untranslated(DescriptionSource::Untranslated, source.to_string()),
+                None,
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_translate() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let pipeline = PokeShakespeare::new(
+            Box::new(|name: &str| match name {
+                "pikachu" => Ok(Lookup::Found("It stores electricity.".into())),
+                "magikarp" => Ok(Lookup::NoDescription),
+                _ => Ok(Lookup::Missing),
+            }),
+            Box::new({
+                let calls = calls.clone();
+                move |s: &str| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    Ok(format!("TRANSLATED: {}", s))
+                }
+            }),
+            Cache::new(4),
+        );
+
+        let pokemon = pipeline.translate("Pikachu").unwrap().unwrap();
+        assert_eq!(pokemon.name, "pikachu");
+        assert_eq!(pokemon.description, "TRANSLATED: It stores electricity.");
+        assert_eq!(pokemon.source, None);
+        pipeline.translate("pikachu").unwrap().unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        assert_eq!(pipeline.translate("magikarp").unwrap(), None);
+        assert_eq!(pipeline.translate("missingno").unwrap(), None);
+        pipeline.translate("p1kachu").unwrap_err();
+    }
+
+    #[test]
+    fn test_translate_with_options() {
+        let pipeline = PokeShakespeare::new(
+            Box::new(|name: &str| match name {
+                "pikachu" => Ok(Lookup::Found("It stores electricity.".into())),
+                _ => Ok(Lookup::Found(" ".into())),
+            }),
+            Box::new(|_: &str| Err(anyhow!("Fun Translations is down"))),
+            Cache::new(4),
+        );
+        pipeline.translate("pikachu").unwrap_err();
+
+        let pipeline = pipeline.with_options(Options {
+            empty_description: Some("No description".into()),
+            fallback_to_source: true,
+            ..Options::default()
+        });
+        let pokemon = pipeline.translate("pikachu").unwrap().unwrap();
+        assert_eq!(pokemon.description, "It stores electricity.");
+        let pokemon = pipeline.translate("missingno").unwrap().unwrap();
+        assert_eq!(pokemon.description, "No description");
+    }
+
+    #[test]
+    fn test_fix_yoda_output() {
+        struct YodaTranslator;
+
+        impl Translator for YodaTranslator {
+            fn translate(&self, source: &str) -> Result<String> {
+                self.translate_styled(source, Style::Yoda).map(|(t, _)| t)
+            }
+
+            fn translate_styled(
+                &self,
+                _: &str,
+                _: Style,
+            ) -> Result<(String, Option<serde_json::Value>)> {
+                Ok(("electricity, it stores  ".into(), None))
+            }
+        }
+
+        let pipeline = |options| {
+            PokeShakespeare::new(
+                Box::new(|_: &str| Ok(Lookup::Found("It stores electricity.".into()))),
+                Box::new(YodaTranslator),
+                Cache::new(4),
+            )
+            .with_options(options)
+        };
+
+        let yoda = Options {
+            style: Style::Yoda,
+            ..Options::default()
+        };
+        let pokemon = pipeline(yoda.clone())
+            .translate("pikachu")
+            .unwrap()
+            .unwrap();
+        assert_eq!(pokemon.description, "electricity, it stores  ");
+
+        let pokemon = pipeline(Options {
+            fix_yoda_output: true,
+            ..yoda
+        })
+        .translate("pikachu")
+        .unwrap()
+        .unwrap();
+        assert_eq!(pokemon.description, "Electricity, it stores.");
+
+        let pokemon = pipeline(Options {
+            fix_yoda_output: true,
+            ..Options::default()
+        })
+        .translate("pikachu")
+        .unwrap()
+        .unwrap();
+        assert_eq!(pokemon.description, "electricity, it stores  ");
+    }
+}
@@ -0,0 +1,320 @@
+//! Prometheus metrics served by `/metrics` and latency percentiles served by `/stats/latency`.
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use log::error;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Data, Request, Response, Rocket, State};
+use serde::Serialize;
+
+use crate::config::Options;
+use crate::services::Cache;
+
+/// Outcome of a `/pokemon` request, used as the `outcome` label of `pokemon_requests_total`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Hit,
+    Miss,
+    NotFound,
+    Error,
+}
+
+impl Outcome {
+    fn label(self) -> &'static str {
+        match self {
+            Outcome::Hit => "hit",
+            Outcome::Miss => "miss",
+            Outcome::NotFound => "notfound",
+            Outcome::Error => "error",
+        }
+    }
+}
+
+/// External service called while handling a request, used as the `service` label of
+/// `upstream_request_duration_seconds`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Upstream {
+    PokeApi,
+    FunTranslations,
+}
+
+impl Upstream {
+    fn label(self) -> &'static str {
+        match self {
+            Upstream::PokeApi => "pokeapi",
+            Upstream::FunTranslations => "funtranslations",
+        }
+    }
+}
+
+/// Request metrics, managed by both `RocketExt` methods.
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    upstream_durations: HistogramVec,
+    latencies: Mutex<HashMap<String, LatencyHistogram>>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let requests = IntCounterVec::new(
+            Opts::new("pokemon_requests_total", "/pokemon requests by outcome."),
+            &["outcome"],
+        )
+        .expect("Invalid metric");
+        let upstream_durations = HistogramVec::new(
+            HistogramOpts::new(
+                "upstream_request_duration_seconds",
+                "Duration of requests to external services.",
+            ),
+            &["service"],
+        )
+        .expect("Invalid metric");
+        let registry = Registry::new();
+        registry
+            .register(Box::new(requests.clone()))
+            .expect("Duplicate metric");
+        registry
+            .register(Box::new(upstream_durations.clone()))
+            .expect("Duplicate metric");
+        Metrics {
+            registry,
+            requests,
+            upstream_durations,
+            latencies: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn record_request(&self, outcome: Outcome) {
+        self.requests.with_label_values(&[outcome.label()]).inc();
+    }
+
+    /// Calls `f`, recording its duration as a request to `upstream`.
+    pub fn time_upstream<T, F: FnOnce() -> T>(&self, upstream: Upstream, f: F) -> T {
+        let _timer = self
+            .upstream_durations
+            .with_label_values(&[upstream.label()])
+            .start_timer();
+        f()
+    }
+
+    /// Records the latency of a request to `endpoint`.
+    pub fn record_latency(&self, endpoint: String, latency: Duration) {
+        self.latencies
+            .lock()
+            .expect("Poisoned latencies lock")
+            .entry(endpoint)
+            .or_default()
+            .record(latency);
+    }
+
+    /// Summarizes the recorded latencies of each endpoint.
+    pub fn latencies(&self) -> BTreeMap<String, LatencySummary> {
+        self.latencies
+            .lock()
+            .expect("Poisoned latencies lock")
+            .iter()
+            .map(|(endpoint, histogram)| (endpoint.clone(), histogram.summary()))
+            .collect()
+    }
+
+    /// Registers the counters of `cache` so they're rendered with the other metrics.
+    pub fn register_cache(&self, cache: &Cache) -> prometheus::Result<()> {
+        for counter in cache.counters() {
+            self.registry.register(Box::new(counter))?;
+        }
+        Ok(())
+    }
+
+    /// Renders all metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buf = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .expect("Failed to encode metrics");
+        String::from_utf8(buf).expect("Metrics aren't valid UTF-8")
+    }
+}
+
+/// Bits of precision kept for each power of two, recorded values are rounded to 1 part in 32.
+const SUB_BUCKET_BITS: u32 = 5;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+
+/// Histogram of latencies in microseconds with logarithmic buckets, in the style of HDR
+/// histograms: each power of two is split in `SUB_BUCKETS` linear buckets, so percentiles are
+/// reported with a bounded relative error whatever their magnitude.
+#[derive(Debug, Default)]
+struct LatencyHistogram {
+    counts: Vec<u64>,
+    total: u64,
+}
+
+impl LatencyHistogram {
+    fn record(&mut self, latency: Duration) {
+        let micros = latency.as_micros().min(u128::from(u64::MAX)) as u64;
+        let index = Self::bucket(micros);
+        if index >= self.counts.len() {
+            self.counts.resize(index + 1, 0);
+        }
+        self.counts[index] += 1;
+        self.total += 1;
+    }
+
+    fn bucket(micros: u64) -> usize {
+        if micros < SUB_BUCKETS {
+            return micros as usize;
+        }
+        let shift = 63 - micros.leading_zeros() - SUB_BUCKET_BITS;
+        (u64::from(shift) * SUB_BUCKETS + (micros >> shift)) as usize
+    }
+
+    /// Middle of the range of values recorded in the bucket at `index`.
+    fn midpoint(index: usize) -> f64 {
+        let index = index as u64;
+        if index < SUB_BUCKETS {
+            return index as f64;
+        }
+        let shift = index / SUB_BUCKETS - 1;
+        let lower = (index % SUB_BUCKETS + SUB_BUCKETS) << shift;
+        lower as f64 + ((1u64 << shift) - 1) as f64 / 2.0
+    }
+
+    /// Smallest recorded latency in milliseconds which is greater than or equal to a fraction `p`
+    /// of all recorded latencies, or 0 if none were recorded.
+    fn percentile(&self, p: f64) -> f64 {
+        let rank = ((p * self.total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Self::midpoint(index) / 1000.0;
+            }
+        }
+        0.0
+    }
+
+    fn summary(&self) -> LatencySummary {
+        LatencySummary {
+            count: self.total,
+            p50_ms: self.percentile(0.5),
+            p95_ms: self.percentile(0.95),
+            p99_ms: self.percentile(0.99),
+        }
+    }
+}
+
+/// Latency percentiles of an endpoint, as listed by `/stats/latency`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+}
+
+/// Time at which Rocket started handling a request.
+struct RequestStart(Instant);
+
+/// Fairing recording the latency of every routed request by method and route path, see
+/// `metrics.latency_percentiles` in `ReadOptions`.
+pub struct TrackLatency;
+
+impl Fairing for TrackLatency {
+    fn info(&self) -> Info {
+        Info {
+            name: "Track Latency",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    fn on_request(&self, request: &mut Request, _: &Data) {
+        request.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    fn on_response(&self, request: &Request, _: &mut Response) {
+        let route = match request.route() {
+            Some(route) => route,
+            None => return,
+        };
+        let enabled = request
+            .guard::<State<Options>>()
+            .succeeded()
+            .map_or(false, |options| options.latency_percentiles);
+        if !enabled {
+            return;
+        }
+        if let Some(metrics) = request.guard::<State<Metrics>>().succeeded() {
+            let start = request.local_cache(|| RequestStart(Instant::now()));
+            metrics.record_latency(
+                format!("{} {}", route.method, route.uri.path()),
+                start.0.elapsed(),
+            );
+        }
+    }
+}
+
+/// Fairing registering the counters of the managed `Cache` with the managed `Metrics`, attached
+/// once both are managed.
+pub struct RegisterCacheMetrics;
+
+impl Fairing for RegisterCacheMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Register Cache Metrics",
+            kind: Kind::Attach,
+        }
+    }
+
+    fn on_attach(&self, rocket: Rocket) -> Result<Rocket, Rocket> {
+        let registered = match (rocket.state::<Metrics>(), rocket.state::<Cache>()) {
+            (Some(metrics), Some(cache)) => metrics.register_cache(cache),
+            _ => return Ok(rocket),
+        };
+        match registered {
+            Ok(()) => Ok(rocket),
+            Err(e) => {
+                error!("Failed to register cache metrics: {}", e);
+                Err(rocket)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_latency_percentiles() {
+        let mut histogram = LatencyHistogram::default();
+        assert!(histogram.percentile(0.5).abs() < f64::EPSILON);
+        // Shuffled so the order of recording doesn't matter
+        for ms in (1..=1000).rev().step_by(2).chain((1..=1000).step_by(2)) {
+            histogram.record(Duration::from_millis(ms));
+        }
+        let summary = histogram.summary();
+        assert_eq!(summary.count, 1000);
+        for &(actual, expected) in &[
+            (summary.p50_ms, 500.0),
+            (summary.p95_ms, 950.0),
+            (summary.p99_ms, 990.0),
+        ] {
+            assert!(
+                (actual - expected).abs() / expected < 0.02,
+                "{} isn't within 2% of {}",
+                actual,
+                expected
+            );
+        }
+
+        let mut histogram = LatencyHistogram::default();
+        histogram.record(Duration::from_micros(7));
+        assert!((histogram.percentile(0.99) - 0.007).abs() < f64::EPSILON);
+    }
+}
@@ -1,45 +1,206 @@
 //! Implementation and abstractions for external services.
 use anyhow::{anyhow, Context, Result};
-use log::warn;
+use log::{debug, warn};
 use serde::{Deserialize, Serialize};
 pub type BoxedPokeApi = Box<dyn PokeApi + Send + Sync>;
 use lru::LruCache;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use prometheus::IntCounter;
 use reqwest::header::HeaderMap;
-use std::sync::Mutex;
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::api::Alpha;
+use crate::api::{Alpha, Species};
 
 /// Abstraction for Poke API access.
 pub trait PokeApi {
-    /// Fetches Pokemon descriptions given their name. Returns `Ok(None)` when either the pokemon
-    /// doesn't exist or it has no english descriptions.
-    fn get_description(&self, name: &str) -> Result<Option<String>>;
+    /// Fetches Pokemon descriptions given their name.
+    fn get_description(&self, name: &str) -> Result<Lookup>;
+
+    /// URL of the resource queried for the pokemon, if there is one.
+    fn source_url(&self, _name: &str) -> Option<String> {
+        None
+    }
+
+    /// Checks whether the service is reachable. Looks up a well-known pokemon by default.
+    fn ping(&self) -> Result<()> {
+        self.get_description("pikachu").map(|_| ())
+    }
+
+    /// Names of all pokemon species, used to suggest names for pokemon which weren't found. No
+    /// names are known by default.
+    fn list_names(&self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
 }
 
 impl<F> PokeApi for F
 where
-    F: Fn(&str) -> Result<Option<String>>,
+    F: Fn(&str) -> Result<Lookup>,
 {
-    fn get_description(&self, name: &str) -> Result<Option<String>> {
+    fn get_description(&self, name: &str) -> Result<Lookup> {
         self(name)
     }
 }
 
+/// Result of a description lookup.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Lookup {
+    /// The pokemon doesn't exist.
+    Missing,
+    /// The pokemon exists but has no description in the configured language or english.
+    NoDescription,
+    Found(String),
+}
+
 /// Poke API accessor. Use the `Default` implementation for the public API at https://pokeapi.co.
 pub struct PokeApiClient {
     pub url: String,
+    /// Path of the species resource relative to `url`. `{name}` is replaced by the URL-encoded
+    /// pokemon name.
+    pub path_template: String,
+    /// Which of the flavor texts is used as the description.
+    pub flavor_text_strategy: FlavorTextStrategy,
+    /// Language of the flavor texts, english ones are used if there are none in this language.
+    pub language: String,
+    client: reqwest::blocking::Client,
 }
 
 impl Default for PokeApiClient {
     fn default() -> Self {
         PokeApiClient {
             url: "https://pokeapi.co/api/v2/pokemon-species/".into(),
+            path_template: "{name}".into(),
+            flavor_text_strategy: FlavorTextStrategy::default(),
+            language: "en".into(),
+            client: ClientOptions::default().build(),
+        }
+    }
+}
+
+/// How to choose among the flavor texts of a species in the same language, which PokeAPI returns in game
+/// version order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlavorTextStrategy {
+    First,
+    Last,
+    Longest,
+    Random,
+}
+
+impl Default for FlavorTextStrategy {
+    fn default() -> Self {
+        FlavorTextStrategy::First
+    }
+}
+
+impl FlavorTextStrategy {
+    /// Picks one of `entries`, whose texts are returned by `text`. Ties for the longest text go
+    /// to the earliest entry.
+    fn select<T, F>(self, mut entries: Vec<T>, text: F) -> Option<T>
+    where
+        F: Fn(&T) -> &str,
+    {
+        if entries.is_empty() {
+            return None;
+        }
+        let index = match self {
+            FlavorTextStrategy::First => 0,
+            FlavorTextStrategy::Last => entries.len() - 1,
+            FlavorTextStrategy::Longest => (0..entries.len())
+                .rev()
+                .max_by_key(|&i| text(&entries[i]).chars().count())?,
+            FlavorTextStrategy::Random => {
+                RandomState::new().build_hasher().finish() as usize % entries.len()
+            }
+        };
+        Some(entries.swap_remove(index))
+    }
+}
+
+impl PokeApiClient {
+    /// Makes requests to PokeAPI with the given user agent and timeout.
+    pub fn with_client_options(mut self, options: &ClientOptions) -> Self {
+        self.client = options.build();
+        self
+    }
+}
+
+/// `User-Agent` sent to external services unless configured otherwise.
+pub const DEFAULT_USER_AGENT: &str = concat!("poke_shakespeare/", env!("CARGO_PKG_VERSION"));
+
+/// Settings of the HTTP clients calling external services.
+#[derive(Clone, Debug)]
+pub struct ClientOptions {
+    pub user_agent: String,
+    /// Requests fail if the service doesn't respond within this time. Defaults to reqwest's.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        ClientOptions {
+            user_agent: DEFAULT_USER_AGENT.into(),
+            timeout: None,
+        }
+    }
+}
+
+impl ClientOptions {
+    fn build(&self) -> reqwest::blocking::Client {
+        let mut builder = reqwest::blocking::Client::builder().user_agent(&self.user_agent);
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
         }
+        builder.build().expect("Failed to build HTTP client")
+    }
+}
+
+/// Returns whether `e` was caused by a request to an external service timing out.
+pub fn is_timeout(e: &anyhow::Error) -> bool {
+    e.chain()
+        .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .any(reqwest::Error::is_timeout)
+}
+
+/// Characters to percent-encode in a path segment, everything except unreserved characters.
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+impl PokeApiClient {
+    /// Species endpoint for the given pokemon.
+    pub fn species_url(&self, name: &str) -> String {
+        let name = utf8_percent_encode(name, PATH_SEGMENT).to_string();
+        format!(
+            "{}{}",
+            self.url,
+            self.path_template.replace("{name}", &name)
+        )
+    }
+
+    /// Endpoint listing every species, the species endpoint up to the pokemon name.
+    pub fn species_list_url(&self) -> String {
+        let prefix = self.path_template.split("{name}").next().unwrap_or("");
+        format!("{}{}", self.url, prefix)
     }
 }
 
 impl PokeApi for PokeApiClient {
-    fn get_description(&self, name: &str) -> Result<Option<String>> {
+    fn get_description(&self, name: &str) -> Result<Lookup> {
         #[derive(Deserialize)]
         struct Species {
             flavor_text_entries: Vec<FlavorText>,
@@ -49,6 +210,8 @@ impl PokeApi for PokeApiClient {
         struct FlavorText {
             flavor_text: String,
             language: NamedResource,
+            #[serde(default)]
+            version: Option<NamedResource>,
         }
 
         #[derive(Deserialize)]
@@ -56,33 +219,82 @@ impl PokeApi for PokeApiClient {
             name: String,
         }
 
-        let resp = reqwest::blocking::get(&format!("{}{}", self.url, name))
+        let resp = self
+            .client
+            .get(&self.species_url(name))
+            .send()
             .context("Failed PokeAPI request")?;
         match resp.status() {
-            reqwest::StatusCode::NOT_FOUND => Ok(None),
+            reqwest::StatusCode::NOT_FOUND => Ok(Lookup::Missing),
             reqwest::StatusCode::OK => {
                 let species: Species =
                     resp.json().context("PokeAPI responded with invalid JSON")?;
-                match species
+                let (mut localized, english): (Vec<_>, Vec<_>) = species
                     .flavor_text_entries
                     .into_iter()
-                    .find(|e| e.language.name == "en")
+                    .filter(|e| e.language.name == self.language || e.language.name == "en")
+                    .partition(|e| e.language.name == self.language);
+                if localized.is_empty() && !english.is_empty() {
+                    warn!(
+                        "Pokemon {} has no {} flavor text available, falling back to english",
+                        name, self.language
+                    );
+                    localized = english;
+                }
+                match self
+                    .flavor_text_strategy
+                    .select(localized, |e: &FlavorText| &e.flavor_text)
                 {
-                    Some(e) => Ok(Some(e.flavor_text)),
+                    Some(e) => {
+                        if let Some(version) = e.version {
+                            debug!("Using {} flavor text of {}", version.name, name);
+                        }
+                        Ok(Lookup::Found(e.flavor_text))
+                    }
                     None => {
-                        warn!("Pokemon {} has no english flavor text available", name);
-                        Ok(None)
+                        warn!("Pokemon {} has no flavor text available", name);
+                        Ok(Lookup::NoDescription)
                     }
                 }
             }
             s => Err(anyhow!(
                 "PokeAPI responded with {}: {}",
                 s,
-                resp.text()
-                    .unwrap_or("<API responded with empty body or unformattable text>".into())
+                resp.text().unwrap_or_else(|_| {
+                    "<API responded with empty body or unformattable text>".into()
+                })
             )),
         }
     }
+
+    fn source_url(&self, name: &str) -> Option<String> {
+        Some(self.species_url(name))
+    }
+
+    fn list_names(&self) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        struct Page {
+            results: Vec<NamedResource>,
+        }
+
+        #[derive(Deserialize)]
+        struct NamedResource {
+            name: String,
+        }
+
+        // The limit is high enough to list every species in a single page
+        let resp = self
+            .client
+            .get(&self.species_list_url())
+            .query(&[("limit", "100000")])
+            .send()
+            .context("Failed PokeAPI request")?;
+        if !resp.status().is_success() {
+            return Err(anyhow!("PokeAPI responded with {}", resp.status()));
+        }
+        let page: Page = resp.json().context("PokeAPI responded with invalid JSON")?;
+        Ok(page.results.into_iter().map(|r| r.name).collect())
+    }
 }
 
 pub type BoxedTranslator = Box<dyn Translator + Send + Sync>;
@@ -91,6 +303,30 @@ pub type BoxedTranslator = Box<dyn Translator + Send + Sync>;
 pub trait Translator {
     /// Translates the given source string to Shakespearean text.
     fn translate(&self, source: &str) -> Result<String>;
+
+    /// Like `translate`, but also returns the raw response of the underlying service, if any, for
+    /// debugging.
+    fn translate_debug(&self, source: &str) -> Result<(String, Option<serde_json::Value>)> {
+        self.translate(source).map(|t| (t, None))
+    }
+
+    /// Like `translate_debug`, but translating to the given style. Only Shakespeare is supported
+    /// by default.
+    fn translate_styled(
+        &self,
+        source: &str,
+        style: Style,
+    ) -> Result<(String, Option<serde_json::Value>)> {
+        match style {
+            Style::Shakespeare => self.translate_debug(source),
+            style => Err(anyhow!("{} translations aren't supported", style)),
+        }
+    }
+
+    /// Checks whether the service is reachable. Translates a single word by default.
+    fn ping(&self) -> Result<()> {
+        self.translate("Hello").map(|_| ())
+    }
 }
 
 impl<F> Translator for F
@@ -102,11 +338,66 @@ where
     }
 }
 
+/// Translation style, each served by the Fun Translations endpoint of the same name.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Style {
+    Shakespeare,
+    Yoda,
+    Pirate,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style::Shakespeare
+    }
+}
+
+impl Style {
+    pub const ALL: [Style; 3] = [Style::Shakespeare, Style::Yoda, Style::Pirate];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Style::Shakespeare => "shakespeare",
+            Style::Yoda => "yoda",
+            Style::Pirate => "pirate",
+        }
+    }
+}
+
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Style {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Style::ALL
+            .iter()
+            .copied()
+            .find(|style| style.name() == s)
+            .ok_or_else(|| anyhow!("Unknown translation style {}", s))
+    }
+}
+
 /// Translation service using the Fun Translations API. Use the `Default` instance to use the
 /// public API at https://api.funtranslations.com.
 pub struct FunTranslationsApi {
+    /// Endpoint of `style` translations, other styles are served by sibling endpoints.
     pub url: String,
+    pub style: Style,
     pub api_key: Option<String>,
+    /// Attempts made while the API responds with 429 or 503, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled on each further retry. A `Retry-After` header in
+    /// seconds takes precedence.
+    pub retry_base_delay: Duration,
+    /// Longest delay before a retry. Backoff delays are capped to it, and requests fail right away
+    /// when the API sends a longer `Retry-After`.
+    pub max_retry_delay: Duration,
     client: reqwest::blocking::Client,
 }
 
@@ -114,14 +405,109 @@ impl Default for FunTranslationsApi {
     fn default() -> Self {
         FunTranslationsApi {
             url: "https://api.funtranslations.com/translate/shakespeare".into(),
+            style: Style::Shakespeare,
             api_key: None,
-            client: reqwest::blocking::Client::new(),
+            max_attempts: 3,
+            retry_base_delay: Duration::from_millis(500),
+            max_retry_delay: Duration::from_secs(10),
+            client: ClientOptions::default().build(),
+        }
+    }
+}
+
+/// Error returned when an external service is still rate limiting or unavailable after retrying.
+#[derive(Debug)]
+pub struct Unavailable {
+    service: &'static str,
+    status: reqwest::StatusCode,
+    attempts: u32,
+    retry_after: Duration,
+}
+
+impl Unavailable {
+    /// How long clients should wait before trying again: the service's `Retry-After` if it sent
+    /// one, the next backoff delay otherwise.
+    pub fn retry_after(&self) -> Duration {
+        self.retry_after
+    }
+
+    /// Whether the service was rate limiting requests rather than down.
+    pub fn is_rate_limited(&self) -> bool {
+        self.status == reqwest::StatusCode::TOO_MANY_REQUESTS
+    }
+}
+
+impl fmt::Display for Unavailable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} responded with {} after {} attempts",
+            self.service, self.status, self.attempts
+        )
+    }
+}
+
+impl std::error::Error for Unavailable {}
+
+/// Returns the `Unavailable` error which caused `e`, if any.
+pub fn unavailable_cause(e: &anyhow::Error) -> Option<&Unavailable> {
+    e.chain()
+        .find_map(|cause| cause.downcast_ref::<Unavailable>())
+}
+
+/// Parses a `Retry-After` header given in seconds. HTTP dates aren't supported.
+fn retry_after(resp: &reqwest::blocking::Response) -> Option<Duration> {
+    let secs = resp.headers().get("Retry-After")?.to_str().ok()?;
+    secs.trim().parse().ok().map(Duration::from_secs)
+}
+
+impl FunTranslationsApi {
+    /// Makes requests to Fun Translations with the given user agent and timeout.
+    pub fn with_client_options(mut self, options: &ClientOptions) -> Self {
+        self.client = options.build();
+        self
+    }
+
+    /// Endpoint of `style` translations, `url` with the last path segment replaced by the style
+    /// name for styles other than `self.style`.
+    pub fn endpoint(&self, style: Style) -> String {
+        if style == self.style {
+            return self.url.clone();
         }
+        let url = self.url.trim_end_matches('/');
+        let parent = url.rsplitn(2, '/').nth(1).unwrap_or(url);
+        format!("{}/{}", parent, style)
     }
 }
 
 impl Translator for FunTranslationsApi {
-    fn translate<'s>(&self, source: &'s str) -> Result<String> {
+    fn translate(&self, source: &str) -> Result<String> {
+        self.translate_debug(source).map(|(t, _)| t)
+    }
+
+    /// Sends a single `HEAD` request to the endpoint, which isn't counted towards the rate limit.
+    /// Only connection failures and server errors count as unreachable.
+    fn ping(&self) -> Result<()> {
+        let resp = self
+            .client
+            .head(&self.url)
+            .send()
+            .context("Failed Fun Translations request")?;
+        if resp.status().is_server_error() {
+            return Err(anyhow!("Fun Translations responded with {}", resp.status()));
+        }
+        Ok(())
+    }
+
+    fn translate_debug(&self, source: &str) -> Result<(String, Option<serde_json::Value>)> {
+        self.translate_styled(source, self.style)
+    }
+
+    fn translate_styled(
+        &self,
+        source: &str,
+        style: Style,
+    ) -> Result<(String, Option<serde_json::Value>)> {
         #[derive(Serialize)]
         struct Request<'s> {
             text: &'s str,
@@ -144,51 +530,1804 @@ impl Translator for FunTranslationsApi {
                 api_key.parse().expect("Invalid Fun Translations API key"),
             );
         }
-        let resp = self
-            .client
-            .post(&self.url)
-            .headers(headers)
-            .form(&Request { text: source })
-            .send()
-            .context("Failed Fun Translations request")?;
+        let url = self.endpoint(style);
+        let mut attempts = 1;
+        let resp = loop {
+            let resp = self
+                .client
+                .post(&url)
+                .headers(headers.clone())
+                .form(&Request { text: source })
+                .send()
+                .context("Failed Fun Translations request")?;
+            let status = resp.status();
+            if status != reqwest::StatusCode::TOO_MANY_REQUESTS
+                && status != reqwest::StatusCode::SERVICE_UNAVAILABLE
+            {
+                break resp;
+            }
+            let backoff = 2u32
+                .checked_pow(attempts - 1)
+                .and_then(|factor| self.retry_base_delay.checked_mul(factor))
+                .map_or(self.max_retry_delay, |d| d.min(self.max_retry_delay));
+            let delay = retry_after(&resp).unwrap_or(backoff);
+            if attempts >= self.max_attempts || delay > self.max_retry_delay {
+                return Err(Unavailable {
+                    service: "Fun Translations",
+                    status,
+                    attempts,
+                    retry_after: delay,
+                }
+                .into());
+            }
+            warn!(
+                "Fun Translations responded with {}, retrying in {:?}",
+                status, delay
+            );
+            thread::sleep(delay);
+            attempts += 1;
+        };
 
         match resp.status() {
             reqwest::StatusCode::OK => {
-                let data: Response = resp
+                let raw: serde_json::Value = resp
                     .json()
                     .context("Fun Translations responded with invalid JSON")?;
-                Ok(data.contents.translated)
+                let data = Response::deserialize(&raw)
+                    .context("Fun Translations responded with invalid JSON")?;
+                Ok((data.contents.translated, Some(raw)))
             }
             s => Err(anyhow!(
                 "Fun Translations responded with {}: {}",
                 s,
-                resp.text()
-                    .unwrap_or("<API responded with empty body or unformattable text>".into())
+                resp.text().unwrap_or_else(|_| {
+                    "<API responded with empty body or unformattable text>".into()
+                })
             )),
         }
     }
 }
 
-pub struct Cache(Mutex<LruCache<Alpha, Option<String>>>);
+/// Bounded map with an eviction policy, see `Eviction`.
+pub trait CacheBackend<K, V>: Send {
+    /// Returns the value under `k`, counting as a use of the entry.
+    fn get(&mut self, k: &K) -> Option<&V>;
+    /// Inserts a value, evicting an entry if the backend is full.
+    fn put(&mut self, k: K, v: V);
+    /// Removes and returns the value under `k`.
+    fn pop(&mut self, k: &K) -> Option<V>;
+    /// Whether there's a value under `k`, without counting as a use.
+    fn contains(&self, k: &K) -> bool;
+    /// Number of stored entries.
+    fn len(&self) -> usize;
+    /// Whether there are no stored entries.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Maximum number of stored entries.
+    fn cap(&self) -> usize;
+    /// Stored entries, from the last to the next to be evicted, without counting as uses.
+    fn entries(&self) -> Vec<(&K, &V)>;
+    /// Stored keys, from the last to the next to be evicted, without counting as uses.
+    fn keys(&self) -> Vec<&K>;
+    /// Key of the entry evicted by the next insertion of a new key.
+    fn peek_evicted(&self) -> Option<&K>;
+}
+
+impl<K, V> CacheBackend<K, V> for LruCache<K, V>
+where
+    K: Hash + Eq + Send,
+    V: Send,
+{
+    fn get(&mut self, k: &K) -> Option<&V> {
+        LruCache::get(self, k)
+    }
+
+    fn put(&mut self, k: K, v: V) {
+        LruCache::put(self, k, v);
+    }
 
-impl Cache {
+    fn pop(&mut self, k: &K) -> Option<V> {
+        LruCache::pop(self, k)
+    }
+
+    fn contains(&self, k: &K) -> bool {
+        LruCache::contains(self, k)
+    }
+
+    fn len(&self) -> usize {
+        LruCache::len(self)
+    }
+
+    fn cap(&self) -> usize {
+        LruCache::cap(self)
+    }
+
+    fn entries(&self) -> Vec<(&K, &V)> {
+        self.iter().collect()
+    }
+
+    fn keys(&self) -> Vec<&K> {
+        self.iter().map(|(k, _)| k).collect()
+    }
+
+    fn peek_evicted(&self) -> Option<&K> {
+        self.peek_lru().map(|(k, _)| k)
+    }
+}
+
+/// Least-frequently-used map, evicts the entry with the fewest uses, or the least recently used
+/// among those.
+pub struct LfuCache<K, V> {
+    capacity: usize,
+    tick: u64,
+    /// Maps keys to their values, use count and the tick of their last use.
+    entries: HashMap<K, (V, u64, u64)>,
+    /// Keys ordered by use count and last use.
+    order: BTreeMap<(u64, u64), K>,
+}
+
+impl<K: Hash + Eq + Clone, V> LfuCache<K, V> {
     pub fn new(capacity: usize) -> Self {
-        Cache(Mutex::new(LruCache::new(capacity)))
+        LfuCache {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+}
+
+impl<K, V> CacheBackend<K, V> for LfuCache<K, V>
+where
+    K: Hash + Eq + Clone + Send,
+    V: Send,
+{
+    fn get(&mut self, k: &K) -> Option<&V> {
+        self.tick += 1;
+        let (v, uses, last_use) = self.entries.get_mut(k)?;
+        self.order.remove(&(*uses, *last_use));
+        *uses += 1;
+        *last_use = self.tick;
+        self.order.insert((*uses, *last_use), k.clone());
+        Some(v)
+    }
+
+    fn put(&mut self, k: K, v: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        let uses = match self.entries.remove(&k) {
+            Some((_, uses, last_use)) => {
+                self.order.remove(&(uses, last_use));
+                uses + 1
+            }
+            None => {
+                if self.entries.len() >= self.capacity {
+                    let evicted = *self.order.keys().next().expect("LfuCache is full");
+                    if let Some(evicted) = self.order.remove(&evicted) {
+                        self.entries.remove(&evicted);
+                    }
+                }
+                1
+            }
+        };
+        self.order.insert((uses, self.tick), k.clone());
+        self.entries.insert(k, (v, uses, self.tick));
+    }
+
+    fn pop(&mut self, k: &K) -> Option<V> {
+        let (v, uses, last_use) = self.entries.remove(k)?;
+        self.order.remove(&(uses, last_use));
+        Some(v)
+    }
+
+    fn contains(&self, k: &K) -> bool {
+        self.entries.contains_key(k)
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn cap(&self) -> usize {
+        self.capacity
+    }
+
+    fn entries(&self) -> Vec<(&K, &V)> {
+        self.order
+            .values()
+            .rev()
+            .map(|k| (k, &self.entries[k].0))
+            .collect()
+    }
+
+    fn keys(&self) -> Vec<&K> {
+        self.order.values().rev().collect()
+    }
+
+    fn peek_evicted(&self) -> Option<&K> {
+        self.order.values().next()
+    }
+}
+
+/// Eviction policy of `Cache`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Eviction {
+    Lru,
+    Lfu,
+}
+
+impl Default for Eviction {
+    fn default() -> Self {
+        Eviction::Lru
+    }
+}
+
+type CachedValue = (Instant, CacheEntry);
+
+impl Eviction {
+    fn backend(self, capacity: usize) -> Box<dyn CacheBackend<CacheKey, CachedValue>> {
+        match self {
+            Eviction::Lru => Box::new(LruCache::new(capacity)),
+            Eviction::Lfu => Box::new(LfuCache::new(capacity)),
+        }
+    }
+}
+
+/// Cache of pokemon lookups. Entries optionally expire after a TTL, in which case they're
+/// calculated again on the next lookup.
+pub struct Cache {
+    entries: Arc<CacheEntries>,
+    /// Calculations in progress, joined by concurrent lookups of the same key. Lock before
+    /// `entries` when holding both.
+    flights: Mutex<HashMap<CacheKey, Arc<Flight>>>,
+    ttl: Option<Duration>,
+    validate_on_read: bool,
+    clock: Box<dyn Fn() -> Instant + Send + Sync>,
+    evictions: IntCounter,
+    expirations: IntCounter,
+    hits: IntCounter,
+    misses: IntCounter,
+    /// Recent mutations and the max number of them kept, if enabled.
+    audit_log: Option<(Mutex<VecDeque<CacheLogEntry>>, usize)>,
+    /// Where the entries are saved, if anywhere, see `persist_to`.
+    persistence: Option<Arc<Persistence>>,
+    /// Identifies the configuration the entries were calculated with, see `with_fingerprint`.
+    fingerprint: String,
+}
+
+type CacheEntries = Mutex<Box<dyn CacheBackend<CacheKey, CachedValue>>>;
+
+/// File a `Cache` is saved to, and whether its entries changed since they were last saved.
+struct Persistence {
+    path: PathBuf,
+    fingerprint: String,
+    dirty: AtomicBool,
+    /// Held while saving, so the flush thread and `Drop` don't write the file concurrently.
+    saving: Mutex<()>,
+}
+
+impl Persistence {
+    /// Saves `entries` if they changed since they were last saved.
+    fn flush(&self, entries: &CacheEntries) {
+        let _saving = self.saving.lock().unwrap();
+        if !self.dirty.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        if let Err(e) = save_entries(entries, &self.fingerprint, &self.path) {
+            self.dirty.store(true, Ordering::Relaxed);
+            warn!("Failed to persist cache: {:#}", e);
+        }
+    }
+}
+
+/// Contents of a file written by `Cache::save`.
+#[derive(Serialize, Deserialize)]
+struct CacheFile<E> {
+    fingerprint: String,
+    entries: Vec<E>,
+}
+
+/// Writes `entries` to `path` as JSON, see `Cache::save`.
+fn save_entries(entries: &CacheEntries, fingerprint: &str, path: &Path) -> Result<()> {
+    let json = {
+        let inner = entries.lock().unwrap();
+        let file = CacheFile {
+            fingerprint: fingerprint.to_string(),
+            entries: inner
+                .entries()
+                .into_iter()
+                .rev()
+                .map(|(k, (_, v))| (k, v))
+                .collect(),
+        };
+        serde_json::to_vec(&file).context("Failed to serialize cache entries")?
+    };
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+    File::create(&tmp_path)
+        .and_then(|mut file| {
+            file.write_all(&json)?;
+            file.sync_all()
+        })
+        .with_context(|| format!("Failed to write cache file {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to replace cache file {}", path.display()))
+}
+
+/// Change made to the entries of a `Cache`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheOp {
+    /// An entry was calculated and stored.
+    Insert,
+    /// An entry was dropped to make room for another.
+    Evict,
+    /// An entry was dropped because it was purged, or stale and not stored again.
+    Remove,
+}
+
+/// Item of the cache audit log, see `Cache::audit_log`.
+#[derive(Clone, Debug, Serialize)]
+pub struct CacheLogEntry {
+    /// RFC3339 timestamp of the change.
+    pub at: String,
+    pub op: CacheOp,
+    pub key: CacheKey,
+}
+
+/// Snapshot of the size and hit rate of a `Cache`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    pub capacity: usize,
+    pub len: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Identifies a cached description: the pokemon species and the way it was translated.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheKey {
+    pub species: Species,
+    /// Only the first sentence of the source description was translated.
+    pub first_sentence: bool,
+    pub style: Style,
+    /// The source description was cached untranslated, for `/pokemon/<name>/raw`.
+    pub raw: bool,
+}
+
+/// Cached result of a pokemon lookup.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CacheEntry {
+    /// Translated description, `None` if the pokemon wasn't found.
+    pub description: Option<String>,
+    /// Whether the pokemon exists without a description, only meaningful without `description`.
+    pub no_description: bool,
+    /// PokeAPI description the translation was made from, if there was one.
+    pub source_description: Option<String>,
+    /// How the description was produced.
+    pub source: DescriptionSource,
+    /// When the description was calculated.
+    pub created_at: SystemTime,
+}
+
+impl CacheEntry {
+    /// Returns false for descriptions which should never be served, i.e. empty ones or ones
+    /// containing form feeds left over from PokeAPI's flavor text.
+    pub fn is_valid(&self) -> bool {
+        self.description
+            .as_ref()
+            .map_or(true, |d| !d.trim().is_empty() && !d.contains('\u{c}'))
+    }
+
+    /// Creates an entry for a translated description calculated now.
+    pub fn new(description: Option<String>) -> Self {
+        CacheEntry {
+            description,
+            no_description: false,
+            source_description: None,
+            source: DescriptionSource::Translated,
+            created_at: SystemTime::now(),
+        }
     }
 }
 
+/// How a description was produced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DescriptionSource {
+    /// Returned by the translator.
+    Translated,
+    /// Source description returned untranslated since it already looks archaic.
+    RawArchaic,
+    /// Source description requested untranslated from `/pokemon/<name>/raw`.
+    Raw,
+    /// Source description returned untranslated since it's shorter than
+    /// `funtranslations.min_chars_to_translate`.
+    RawShort,
+    /// Pre-written description served because the upstream services failed.
+    Static,
+    /// Source description returned untranslated because the translator failed. Never cached.
+    Untranslated,
+}
+
 impl Cache {
-    pub fn get_or_calculate<F>(&self, k: Alpha, f: F) -> Result<Option<String>>
-    where
-        F: FnOnce() -> Result<Option<String>>,
-    {
-        let mut inner = self.0.lock().unwrap();
-        if let Some(v) = inner.get(&k) {
-            Ok(v.clone())
-        } else {
-            let v = f()?;
-            inner.put(k, v.clone());
-            Ok(v)
+    /// Creates an LRU cache whose entries never expire.
+    pub fn new(capacity: usize) -> Self {
+        Cache::with_eviction(capacity, Eviction::Lru, None)
+    }
+
+    /// Creates an LRU cache whose entries expire `ttl` after being calculated.
+    pub fn with_ttl(capacity: usize, ttl: Duration) -> Self {
+        Cache::with_eviction(capacity, Eviction::Lru, Some(ttl))
+    }
+
+    /// Creates a cache with the given eviction policy, whose entries expire `ttl` after being
+    /// calculated if specified.
+    pub fn with_eviction(capacity: usize, eviction: Eviction, ttl: Option<Duration>) -> Self {
+        Cache::with_clock(eviction.backend(capacity), ttl, Instant::now)
+    }
+
+    /// Makes lookups calculate cached entries again if they aren't valid, see
+    /// `CacheEntry::is_valid`.
+    pub fn validate_on_read(mut self, enabled: bool) -> Self {
+        self.validate_on_read = enabled;
+        self
+    }
+
+    /// Tags saved entries with `fingerprint`, identifying the configuration they were calculated
+    /// with. `load` discards files saved with a different fingerprint.
+    pub fn with_fingerprint(mut self, fingerprint: String) -> Self {
+        self.fingerprint = fingerprint;
+        self
+    }
+
+    /// Saves the entries to `path` like `save` every `interval` if they changed, from a
+    /// background thread, and when the cache is dropped. Entries are tagged with the fingerprint
+    /// set before calling this.
+    pub fn persist_to(mut self, path: PathBuf, interval: Duration) -> Self {
+        let persistence = Arc::new(Persistence {
+            path,
+            fingerprint: self.fingerprint.clone(),
+            dirty: AtomicBool::new(false),
+            saving: Mutex::new(()),
+        });
+        let entries = Arc::downgrade(&self.entries);
+        let flushed = persistence.clone();
+        // The thread stops once the cache is dropped
+        thread::Builder::new()
+            .name("cache-flush".into())
+            .spawn(move || loop {
+                thread::sleep(interval);
+                match entries.upgrade() {
+                    Some(entries) => flushed.flush(&entries),
+                    None => break,
+                }
+            })
+            .expect("Failed to spawn cache flush thread");
+        self.persistence = Some(persistence);
+        self
+    }
+
+    /// Writes all entries to `path` as JSON, from the least to the most recently used with LRU
+    /// eviction, so `load` restores them in the same order. The entries are written to a
+    /// temporary file renamed to `path`, so the file is never left partially written.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        save_entries(&self.entries, &self.fingerprint, path)
+    }
+
+    /// Stores the entries written to `path` by `save`, skipping expired ones. Returns how many
+    /// entries were stored, 0 if the file doesn't exist. Fails without storing any entries if the
+    /// file was saved with a different fingerprint.
+    pub fn load(&self, path: &Path) -> Result<usize> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to open cache file {}", path.display()))
+            }
+        };
+        let file: CacheFile<(CacheKey, CacheEntry)> =
+            serde_json::from_reader(io::BufReader::new(file))
+                .with_context(|| format!("Invalid cache file {}", path.display()))?;
+        if file.fingerprint != self.fingerprint {
+            return Err(anyhow!(
+                "Cache file {} was saved with a different configuration",
+                path.display()
+            ));
+        }
+        let entries = file.entries;
+        let mut inner = self.entries.lock().unwrap();
+        let now = (self.clock)();
+        let mut loaded = 0;
+        for (k, v) in entries {
+            // Entries expire relative to when they were calculated, not loaded
+            let age = SystemTime::now()
+                .duration_since(v.created_at)
+                .unwrap_or_default();
+            let stored_at = now.checked_sub(age).unwrap_or(now);
+            if self.is_fresh(stored_at, now) {
+                inner.put(k, (stored_at, v));
+                loaded += 1;
+            }
         }
+        Ok(loaded)
+    }
+
+    /// Records the last `size` mutations of the cache, see `audit_log`.
+    pub fn with_audit_log(mut self, size: usize) -> Self {
+        self.audit_log = Some((Mutex::new(VecDeque::with_capacity(size)), size));
+        self
+    }
+
+    /// Recent insertions, evictions and removals from the oldest to the newest, `None` unless
+    /// enabled with `with_audit_log`.
+    pub fn audit_log(&self) -> Option<Vec<CacheLogEntry>> {
+        let (log, _) = self.audit_log.as_ref()?;
+        Some(log.lock().unwrap().iter().cloned().collect())
+    }
+
+    fn record(&self, op: CacheOp, key: &CacheKey) {
+        if let Some(ref persistence) = self.persistence {
+            persistence.dirty.store(true, Ordering::Relaxed);
+        }
+        if let Some((log, size)) = &self.audit_log {
+            let mut log = log.lock().unwrap();
+            if log.len() >= *size {
+                log.pop_front();
+            }
+            log.push_back(CacheLogEntry {
+                at: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+                op,
+                key: key.clone(),
+            });
+        }
+    }
+
+    /// Number of entries evicted to make room for new ones.
+    pub fn evictions(&self) -> u64 {
+        self.evictions.get() as u64
+    }
+
+    /// Number of entries found expired on lookup.
+    pub fn expirations(&self) -> u64 {
+        self.expirations.get() as u64
+    }
+
+    /// Counters of evictions, expirations, hits and misses, to register with a Prometheus
+    /// registry.
+    pub fn counters(&self) -> Vec<IntCounter> {
+        vec![
+            self.evictions.clone(),
+            self.expirations.clone(),
+            self.hits.clone(),
+            self.misses.clone(),
+        ]
+    }
+
+    /// Current size, capacity and number of lookups that did and didn't find a usable entry.
+    pub fn stats(&self) -> CacheStats {
+        let inner = self.entries.lock().unwrap();
+        CacheStats {
+            capacity: inner.cap(),
+            len: inner.len(),
+            hits: self.hits.get() as u64,
+            misses: self.misses.get() as u64,
+        }
+    }
+
+    fn is_fresh(&self, stored_at: Instant, now: Instant) -> bool {
+        self.ttl
+            .map_or(true, |ttl| now.saturating_duration_since(stored_at) < ttl)
+    }
+
+    fn with_clock<C>(
+        backend: Box<dyn CacheBackend<CacheKey, CachedValue>>,
+        ttl: Option<Duration>,
+        clock: C,
+    ) -> Self
+    where
+        C: 'static + Fn() -> Instant + Send + Sync,
+    {
+        Cache {
+            entries: Arc::new(Mutex::new(backend)),
+            flights: Mutex::new(HashMap::new()),
+            ttl,
+            validate_on_read: false,
+            clock: Box::new(clock),
+            evictions: counter(
+                "cache_evictions_total",
+                "Cache entries evicted to make room for new ones.",
+            ),
+            expirations: counter(
+                "cache_expirations_total",
+                "Cache entries found expired on lookup.",
+            ),
+            hits: counter(
+                "cache_hits_total",
+                "Cache lookups which found a usable entry.",
+            ),
+            misses: counter(
+                "cache_misses_total",
+                "Cache lookups which didn't find a usable entry.",
+            ),
+            audit_log: None,
+            persistence: None,
+            fingerprint: String::new(),
+        }
+    }
+}
+
+impl Cache {
+    /// Cached keys, from the most to the least recently used with LRU eviction.
+    pub fn order(&self) -> Vec<CacheKey> {
+        let inner = self.entries.lock().unwrap();
+        inner.keys().into_iter().cloned().collect()
+    }
+
+    /// Removes all cached translations of `species`, returns whether there were any.
+    pub fn remove(&self, species: &Species) -> bool {
+        let mut inner = self.entries.lock().unwrap();
+        let mut removed = false;
+        for &raw in &[false, true] {
+            for &first_sentence in &[false, true] {
+                for &style in &Style::ALL {
+                    let key = CacheKey {
+                        species: species.clone(),
+                        first_sentence,
+                        style,
+                        raw,
+                    };
+                    if inner.pop(&key).is_some() {
+                        self.record(CacheOp::Remove, &key);
+                        removed = true;
+                    }
+                }
+            }
+        }
+        removed
+    }
+
+    /// Returns the entry under `k`, calculating and storing it with `f` if it's missing or stale.
+    /// Concurrent calls for the same key wait for a single calculation instead of repeating it,
+    /// and retry if it fails.
+    pub fn get_or_calculate<F, E>(&self, k: CacheKey, f: F) -> std::result::Result<CacheEntry, E>
+    where
+        F: FnOnce() -> std::result::Result<CacheEntry, E>,
+    {
+        self.get_or_calculate_if(k, f, |_| true)
+    }
+
+    /// Like `get_or_calculate`, but calculated entries are only stored if `keep` returns true.
+    pub fn get_or_calculate_if<F, P, E>(
+        &self,
+        k: CacheKey,
+        f: F,
+        keep: P,
+    ) -> std::result::Result<CacheEntry, E>
+    where
+        F: FnOnce() -> std::result::Result<CacheEntry, E>,
+        P: FnOnce(&CacheEntry) -> bool,
+    {
+        loop {
+            let (flight, now) = match self.lookup(&k) {
+                CacheLookup::Cached(v) => return Ok(v),
+                CacheLookup::Join(flight) => {
+                    // Retry if the calculation failed, either calculating or finding the entry
+                    // stored by another lookup
+                    if let Some(v) = flight.wait() {
+                        self.hits.inc();
+                        return Ok(v);
+                    }
+                    continue;
+                }
+                CacheLookup::Lead(flight, now) => (flight, now),
+            };
+            // Lookups of other keys proceed while calculating, lookups of this one join the flight
+            let mut landing = Landing {
+                cache: self,
+                key: &k,
+                flight,
+                result: None,
+            };
+            let v = f()?;
+            let mut inner = self.entries.lock().unwrap();
+            if keep(&v) {
+                if !inner.contains(&k) && inner.len() >= inner.cap() {
+                    self.evictions.inc();
+                    if let Some(evicted) = inner.peek_evicted() {
+                        self.record(CacheOp::Evict, evicted);
+                    }
+                }
+                self.record(CacheOp::Insert, &k);
+                inner.put(k.clone(), (now, v.clone()));
+            } else if inner.pop(&k).is_some() {
+                self.record(CacheOp::Remove, &k);
+            }
+            landing.result = Some(v.clone());
+            return Ok(v);
+        }
+    }
+
+    /// Returns the usable entry under `k`, or the calculation to join or lead otherwise.
+    fn lookup(&self, k: &CacheKey) -> CacheLookup {
+        let mut flights = self.flights.lock().unwrap();
+        if let Some(flight) = flights.get(k) {
+            return CacheLookup::Join(flight.clone());
+        }
+        let mut inner = self.entries.lock().unwrap();
+        let now = (self.clock)();
+        // Unexpired entries are only recalculated if they're invalid
+        if let Some((stored_at, v)) = inner.get(k) {
+            let fresh = self.is_fresh(*stored_at, now);
+            if fresh && (!self.validate_on_read || v.is_valid()) {
+                self.hits.inc();
+                return CacheLookup::Cached(v.clone());
+            }
+            if !fresh {
+                self.expirations.inc();
+            }
+        }
+        self.misses.inc();
+        let flight = Arc::new(Flight::default());
+        flights.insert(k.clone(), flight.clone());
+        CacheLookup::Lead(flight, now)
+    }
+}
+
+/// Outcome of `Cache::lookup`.
+enum CacheLookup {
+    Cached(CacheEntry),
+    /// Another caller is calculating the entry.
+    Join(Arc<Flight>),
+    /// The caller must calculate the entry, stored at the given time.
+    Lead(Arc<Flight>, Instant),
+}
+
+/// Calculation of a cache entry shared by concurrent lookups of its key.
+#[derive(Default)]
+struct Flight {
+    /// `None` while in progress, then the calculated entry or `None` if calculating it failed.
+    result: Mutex<Option<Option<CacheEntry>>>,
+    done: Condvar,
+}
+
+impl Flight {
+    /// Blocks until the calculation finishes, returns the entry unless it failed.
+    fn wait(&self) -> Option<CacheEntry> {
+        let mut result = self.result.lock().unwrap();
+        while result.is_none() {
+            result = self.done.wait(result).unwrap();
+        }
+        result.clone().unwrap()
+    }
+}
+
+/// Finishes a `Flight` when dropped, with `result` or as failed if there's none. Completes the
+/// flight even if calculating the entry panics.
+struct Landing<'a> {
+    cache: &'a Cache,
+    key: &'a CacheKey,
+    flight: Arc<Flight>,
+    result: Option<CacheEntry>,
+}
+
+/// Creates a counter of `Cache` operations.
+fn counter(name: &str, help: &str) -> IntCounter {
+    IntCounter::new(name, help).expect("Invalid metric")
+}
+
+impl Drop for Cache {
+    fn drop(&mut self) {
+        if let Some(ref persistence) = self.persistence {
+            persistence.flush(&self.entries);
+        }
+    }
+}
+
+impl Drop for Landing<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut flights) = self.cache.flights.lock() {
+            flights.remove(self.key);
+        }
+        if let Ok(mut result) = self.flight.result.lock() {
+            *result = Some(self.result.take());
+        }
+        self.flight.done.notify_all();
+    }
+}
+
+/// Set of known species names used to reject unknown pokemon without querying PokeAPI.
+pub struct KnownNames(Option<HashSet<String>>);
+
+impl KnownNames {
+    /// Creates an instance which accepts every name.
+    pub fn unavailable() -> Self {
+        KnownNames(None)
+    }
+
+    pub fn new<I>(names: I) -> Self
+    where
+        I: IntoIterator<Item = String>,
+    {
+        KnownNames(Some(names.into_iter().collect()))
+    }
+
+    /// Reads names from a file containing one name per line, ignoring blank lines.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(KnownNames::new(
+            contents
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .map(str::to_lowercase),
+        ))
+    }
+
+    /// Returns false if `name` is definitely not a pokemon.
+    pub fn may_exist(&self, name: &str) -> bool {
+        self.0.as_ref().map_or(true, |names| names.contains(name))
+    }
+
+    /// Returns true if `name` is definitely a pokemon.
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.as_ref().map_or(false, |names| names.contains(name))
+    }
+
+    /// Returns the known names, if they're available.
+    pub fn names(&self) -> Option<&HashSet<String>> {
+        self.0.as_ref()
+    }
+}
+
+/// Species names fetched with `PokeApi::list_names` on first use, to suggest names for pokemon
+/// which weren't found.
+pub struct SpeciesNames {
+    state: Mutex<SpeciesNamesState>,
+    retry_delay: Duration,
+}
+
+#[derive(Default)]
+struct SpeciesNamesState {
+    names: Option<Arc<Vec<String>>>,
+    fetching: bool,
+    last_failure: Option<Instant>,
+}
+
+impl Default for SpeciesNames {
+    fn default() -> Self {
+        SpeciesNames::new(Duration::from_secs(60))
+    }
+}
+
+impl SpeciesNames {
+    /// Creates an instance which waits `retry_delay` before fetching names again after a failure.
+    pub fn new(retry_delay: Duration) -> Self {
+        SpeciesNames {
+            state: Mutex::new(SpeciesNamesState::default()),
+            retry_delay,
+        }
+    }
+
+    /// Returns the species names, fetching them from `pokeapi` unless they were already. Returns
+    /// `None` while another call is fetching them, or if fetching them failed less than
+    /// `retry_delay` ago.
+    pub fn get(&self, pokeapi: &dyn PokeApi) -> Option<Arc<Vec<String>>> {
+        {
+            let mut state = self.state.lock().unwrap();
+            if let Some(ref names) = state.names {
+                return Some(names.clone());
+            }
+            let backing_off = state
+                .last_failure
+                .map_or(false, |t| t.elapsed() < self.retry_delay);
+            if state.fetching || backing_off {
+                return None;
+            }
+            state.fetching = true;
+        }
+
+        // Fetched without holding the lock so other requests aren't blocked on PokeAPI
+        let result = pokeapi.list_names();
+        let mut state = self.state.lock().unwrap();
+        state.fetching = false;
+        match result {
+            Ok(list) => {
+                let names = Arc::new(list);
+                state.names = Some(names.clone());
+                Some(names)
+            }
+            Err(e) => {
+                warn!("Failed to list species names: {:#}", e);
+                state.last_failure = Some(Instant::now());
+                None
+            }
+        }
+    }
+}
+
+/// Memoizes the canonical form of raw names sent by clients.
+pub struct CanonicalNames(Option<Mutex<LruCache<Alpha, Alpha>>>);
+
+impl CanonicalNames {
+    /// Creates an instance which canonicalizes every name.
+    pub fn disabled() -> Self {
+        CanonicalNames(None)
+    }
+
+    pub fn new(capacity: usize) -> Self {
+        CanonicalNames(Some(Mutex::new(LruCache::new(capacity))))
+    }
+
+    /// Returns the memoized canonical form of `raw`, or calculates it with `f`.
+    pub fn get_or_canonicalize<F>(&self, raw: &Alpha, f: F) -> Alpha
+    where
+        F: FnOnce(&Alpha) -> Alpha,
+    {
+        match self.0 {
+            Some(ref names) => {
+                let mut names = names.lock().unwrap();
+                if let Some(name) = names.get(raw) {
+                    return name.clone();
+                }
+                let name = f(raw);
+                names.put(raw.clone(), name.clone());
+                name
+            }
+            None => f(raw),
+        }
+    }
+}
+
+/// Sheds load while the rate of cache misses over a rolling window is above a threshold, to
+/// protect the rate-limited upstream APIs from cache-busting traffic.
+pub struct LoadShedder(Option<LoadShedderInner>);
+
+struct LoadShedderInner {
+    threshold: f64,
+    window: Duration,
+    min_requests: u64,
+    buckets: Mutex<VecDeque<Bucket>>,
+}
+
+/// Hits and misses recorded during one `BUCKET_WIDTH`.
+struct Bucket {
+    start: Instant,
+    hits: u64,
+    misses: u64,
+}
+
+const BUCKET_WIDTH: Duration = Duration::from_secs(1);
+
+impl LoadShedder {
+    /// Creates a shedder which never sheds.
+    pub fn disabled() -> Self {
+        LoadShedder(None)
+    }
+
+    /// Creates a shedder which sheds when the miss rate over the last `window` is above
+    /// `threshold`, as long as at least `min_requests` were recorded in the window.
+    pub fn new(threshold: f64, window: Duration, min_requests: u64) -> Self {
+        LoadShedder(Some(LoadShedderInner {
+            threshold,
+            window,
+            min_requests,
+            buckets: Mutex::new(VecDeque::new()),
+        }))
+    }
+
+    /// Records a cache hit or miss.
+    pub fn record(&self, hit: bool) {
+        if let Some(ref inner) = self.0 {
+            let now = Instant::now();
+            let mut buckets = inner.buckets.lock().unwrap();
+            inner.expire(&mut buckets, now);
+            let bucket = match buckets.back_mut() {
+                Some(b) if now.duration_since(b.start) < BUCKET_WIDTH => b,
+                _ => {
+                    buckets.push_back(Bucket {
+                        start: now,
+                        hits: 0,
+                        misses: 0,
+                    });
+                    buckets.back_mut().unwrap()
+                }
+            };
+            if hit {
+                bucket.hits += 1;
+            } else {
+                bucket.misses += 1;
+            }
+        }
+    }
+
+    /// Whether cache misses should currently be rejected.
+    pub fn is_shedding(&self) -> bool {
+        match self.0 {
+            Some(ref inner) => {
+                let mut buckets = inner.buckets.lock().unwrap();
+                inner.expire(&mut buckets, Instant::now());
+                let (hits, misses) = buckets
+                    .iter()
+                    .fold((0, 0), |(h, m), b| (h + b.hits, m + b.misses));
+                let total = hits + misses;
+                total > 0
+                    && total >= inner.min_requests
+                    && misses as f64 / total as f64 > inner.threshold
+            }
+            None => false,
+        }
+    }
+}
+
+impl LoadShedderInner {
+    fn expire(&self, buckets: &mut VecDeque<Bucket>, now: Instant) {
+        while buckets
+            .front()
+            .map_or(false, |b| now.duration_since(b.start) >= self.window)
+        {
+            buckets.pop_front();
+        }
+    }
+}
+
+/// Log of failed translations, appended to a file as JSON lines so they can be reprocessed later.
+pub struct DeadLetterLog(Option<Mutex<File>>);
+
+impl DeadLetterLog {
+    /// Creates a log which discards all records.
+    pub fn disabled() -> Self {
+        DeadLetterLog(None)
+    }
+
+    /// Opens the file at `path` for appending, creating it if necessary.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(DeadLetterLog(Some(Mutex::new(file))))
+    }
+
+    /// Appends a failed translation of `source`, the description for pokemon `name`.
+    pub fn record(&self, name: &str, source: &str, error: &anyhow::Error) -> Result<()> {
+        #[derive(Serialize)]
+        struct Record<'a> {
+            name: &'a str,
+            source: &'a str,
+            error: String,
+        }
+
+        if let Some(ref file) = self.0 {
+            let mut line = serde_json::to_vec(&Record {
+                name,
+                source,
+                error: error.to_string(),
+            })?;
+            line.push(b'\n');
+            file.lock()
+                .unwrap()
+                .write_all(&line)
+                .context("Failed to write dead letter")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::cell::Cell;
+    use std::fs;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::{mpsc, Barrier};
+
+    use crate::test_utils::MockServer;
+
+    #[test]
+    fn test_eviction_policies() {
+        fn accesses(backend: &mut dyn CacheBackend<&'static str, u32>) {
+            backend.put("a", 1);
+            backend.get(&"a");
+            backend.get(&"a");
+            backend.put("b", 2);
+            backend.put("c", 3);
+        }
+
+        let mut lru = LruCache::new(2);
+        accesses(&mut lru);
+        assert_eq!(CacheBackend::get(&mut lru, &"a"), None);
+        assert_eq!(CacheBackend::get(&mut lru, &"b"), Some(&2));
+
+        let mut lfu = LfuCache::new(2);
+        accesses(&mut lfu);
+        assert_eq!(lfu.get(&"a"), Some(&1));
+        assert_eq!(lfu.get(&"b"), None);
+        assert_eq!(lfu.get(&"c"), Some(&3));
+    }
+
+    #[test]
+    fn test_cache_validate_on_read() {
+        let key = CacheKey {
+            species: Species::Id(25),
+            first_sentence: false,
+            style: Style::Shakespeare,
+            raw: false,
+        };
+        let bad = || Ok::<_, ()>(CacheEntry::new(Some("desc\u{c}with form feed".into())));
+        let good = || Ok::<_, ()>(CacheEntry::new(Some("desc".into())));
+
+        let cache = Cache::new(4);
+        cache.get_or_calculate(key.clone(), bad).unwrap();
+        let entry = cache.get_or_calculate(key.clone(), good).unwrap();
+        assert_eq!(
+            entry.description.as_deref(),
+            Some("desc\u{c}with form feed")
+        );
+
+        let cache = Cache::new(4).validate_on_read(true);
+        cache.get_or_calculate(key.clone(), bad).unwrap();
+        let entry = cache.get_or_calculate(key.clone(), good).unwrap();
+        assert_eq!(entry.description.as_deref(), Some("desc"));
+        let entry = cache
+            .get_or_calculate(key, || Err(()))
+            .expect("Recomputed entry should be cached");
+        assert_eq!(entry.description.as_deref(), Some("desc"));
+    }
+
+    #[test]
+    fn test_cache_order() {
+        let key = |id| CacheKey {
+            species: Species::Id(id),
+            first_sentence: false,
+            style: Style::Shakespeare,
+            raw: false,
+        };
+        let cache = Cache::new(3);
+        for &id in &[1, 2, 3, 1, 4] {
+            cache
+                .get_or_calculate(key(id), || Ok::<_, ()>(CacheEntry::new(None)))
+                .unwrap();
+        }
+        let order = cache.order();
+        assert_eq!(order, vec![key(4), key(1), key(3)]);
+        // Reading the order doesn't count as a use
+        assert_eq!(cache.order(), order);
+    }
+
+    #[test]
+    fn test_cache_remove() {
+        let species = Species::Name(Alpha::try_new("pikachu".into()).unwrap());
+        let cache = Cache::new(4);
+        for &(style, raw) in &[
+            (Style::Shakespeare, false),
+            (Style::Yoda, false),
+            (Style::Shakespeare, true),
+        ] {
+            let key = CacheKey {
+                species: species.clone(),
+                first_sentence: false,
+                style,
+                raw,
+            };
+            cache
+                .get_or_calculate(key, || Ok::<_, ()>(CacheEntry::new(None)))
+                .unwrap();
+        }
+
+        assert!(cache.remove(&species));
+        assert_eq!(cache.stats().len, 0);
+        assert!(!cache.remove(&species));
+        assert!(!cache.remove(&Species::Id(25)));
+    }
+
+    #[test]
+    fn test_cache_ttl() {
+        let now = Arc::new(Mutex::new(Instant::now()));
+        let cache = Cache::with_clock(Eviction::Lru.backend(4), Some(Duration::from_secs(60)), {
+            let now = now.clone();
+            move || *now.lock().unwrap()
+        });
+        let key = CacheKey {
+            species: Species::Name(Alpha::try_new("pikachu".into()).unwrap()),
+            first_sentence: false,
+            style: Style::Shakespeare,
+            raw: false,
+        };
+        let calls = Cell::new(0);
+        let calculate = || {
+            calls.set(calls.get() + 1);
+            Ok::<_, ()>(CacheEntry::new(Some(format!("desc {}", calls.get()))))
+        };
+
+        let entry = cache.get_or_calculate(key.clone(), calculate).unwrap();
+        assert_eq!(entry.description.as_deref(), Some("desc 1"));
+        *now.lock().unwrap() += Duration::from_secs(59);
+        let entry = cache.get_or_calculate(key.clone(), calculate).unwrap();
+        assert_eq!(entry.description.as_deref(), Some("desc 1"));
+        *now.lock().unwrap() += Duration::from_secs(1);
+        let entry = cache.get_or_calculate(key.clone(), calculate).unwrap();
+        assert_eq!(entry.description.as_deref(), Some("desc 2"));
+        assert_eq!(calls.get(), 2);
+
+        let cache = Cache::new(4);
+        cache.get_or_calculate(key.clone(), calculate).unwrap();
+        cache.get_or_calculate(key, calculate).unwrap();
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn test_cache_eviction_counters() {
+        let now = Arc::new(Mutex::new(Instant::now()));
+        let cache = Cache::with_clock(Eviction::Lru.backend(2), Some(Duration::from_secs(60)), {
+            let now = now.clone();
+            move || *now.lock().unwrap()
+        });
+        let key = |id| CacheKey {
+            species: Species::Id(id),
+            first_sentence: false,
+            style: Style::Shakespeare,
+            raw: false,
+        };
+        let calculate = || Ok::<_, ()>(CacheEntry::new(Some("desc".into())));
+
+        for id in 1..=3 {
+            cache.get_or_calculate(key(id), calculate).unwrap();
+        }
+        assert_eq!(cache.evictions(), 1);
+        assert_eq!(cache.expirations(), 0);
+
+        *now.lock().unwrap() += Duration::from_secs(60);
+        cache.get_or_calculate(key(3), calculate).unwrap();
+        assert_eq!(cache.evictions(), 1);
+        assert_eq!(cache.expirations(), 1);
+    }
+
+    #[test]
+    fn test_cache_persistence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.json");
+        let key = |name: &str| CacheKey {
+            species: Species::Name(Alpha::try_new(name.into()).unwrap()),
+            first_sentence: false,
+            style: Style::Shakespeare,
+            raw: false,
+        };
+        let entries = vec![
+            (key("pikachu"), CacheEntry::new(Some("Pika pika".into()))),
+            // Negative entries are persisted too
+            (key("missingno"), CacheEntry::new(None)),
+            (
+                CacheKey {
+                    species: Species::Id(25),
+                    ..key("pikachu")
+                },
+                CacheEntry {
+                    no_description: true,
+                    ..CacheEntry::new(None)
+                },
+            ),
+        ];
+
+        {
+            let cache = Cache::new(4).persist_to(path.clone(), Duration::from_secs(3600));
+            for (k, v) in &entries {
+                let v = v.clone();
+                cache
+                    .get_or_calculate(k.clone(), || Ok::<_, ()>(v))
+                    .unwrap();
+            }
+        }
+
+        let cache = Cache::new(4);
+        assert_eq!(cache.load(&path).unwrap(), 3);
+        let mut order = cache.order();
+        order.reverse();
+        assert_eq!(
+            order,
+            entries.iter().map(|(k, _)| k.clone()).collect::<Vec<_>>()
+        );
+        for (k, v) in entries {
+            let loaded = cache
+                .get_or_calculate(k, || Err("Entry wasn't loaded"))
+                .unwrap();
+            assert_eq!(loaded, v);
+        }
+
+        // Entries saved with another configuration are discarded
+        let cache = Cache::new(4).with_fingerprint("mock translator".into());
+        cache.load(&path).unwrap_err();
+        assert!(cache.order().is_empty());
+
+        assert_eq!(
+            Cache::new(4)
+                .load(&dir.path().join("missing.json"))
+                .unwrap(),
+            0
+        );
+        fs::write(&path, "not json").unwrap();
+        Cache::new(4).load(&path).unwrap_err();
+    }
+
+    #[test]
+    fn test_cache_flush() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.json");
+        let saved = || Cache::new(4).load(&path).unwrap();
+        let insert = |cache: &Cache, id| {
+            let k = CacheKey {
+                species: Species::Id(id),
+                first_sentence: false,
+                style: Style::Shakespeare,
+                raw: false,
+            };
+            cache
+                .get_or_calculate(k, || Ok::<_, ()>(CacheEntry::new(None)))
+                .unwrap();
+        };
+
+        let wait_for_save = |n| {
+            for _ in 0..100 {
+                if saved() == n {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+            panic!("Cache wasn't saved with {} entries", n);
+        };
+
+        // Changes are saved periodically while the cache is in use
+        let cache = Cache::new(4).persist_to(path.clone(), Duration::from_millis(10));
+        insert(&cache, 1);
+        wait_for_save(1);
+        insert(&cache, 2);
+        wait_for_save(2);
+        assert!(cache.remove(&Species::Id(1)));
+        wait_for_save(1);
+        assert!(!dir.path().join("cache.json.tmp").exists());
+        drop(cache);
+
+        // Changes since the last save are saved when the cache is dropped
+        let cache = Cache::new(4).persist_to(path.clone(), Duration::from_secs(3600));
+        insert(&cache, 3);
+        insert(&cache, 4);
+        assert_eq!(saved(), 1);
+        drop(cache);
+        assert_eq!(saved(), 2);
+    }
+
+    #[test]
+    fn test_cache_audit_log() {
+        let key = |id| CacheKey {
+            species: Species::Id(id),
+            first_sentence: false,
+            style: Style::Shakespeare,
+            raw: false,
+        };
+        let calculate = || Ok::<_, ()>(CacheEntry::new(Some("desc".into())));
+        assert!(Cache::new(2).audit_log().is_none());
+
+        let cache = Cache::new(2).with_audit_log(3);
+        for id in 1..=3 {
+            cache.get_or_calculate(key(id), calculate).unwrap();
+        }
+        cache.get_or_calculate(key(3), calculate).unwrap();
+        cache.remove(&Species::Id(2));
+        let log: Vec<_> = cache
+            .audit_log()
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.op, entry.key))
+            .collect();
+        assert_eq!(
+            log,
+            vec![
+                (CacheOp::Evict, key(1)),
+                (CacheOp::Insert, key(3)),
+                (CacheOp::Remove, key(2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cache_coalesces_lookups() {
+        let cache = Arc::new(Cache::new(4));
+        let pokeapi = Arc::new(|_: &str| {
+            thread::sleep(Duration::from_millis(100));
+            Ok(Lookup::Found("desc".into()))
+        });
+        let calls = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(Barrier::new(8));
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let (cache, pokeapi, calls, barrier) = (
+                    cache.clone(),
+                    pokeapi.clone(),
+                    calls.clone(),
+                    barrier.clone(),
+                );
+                thread::spawn(move || {
+                    let key = CacheKey {
+                        species: Species::Name(Alpha::try_new("bulbasaur".into()).unwrap()),
+                        first_sentence: false,
+                        style: Style::Shakespeare,
+                        raw: false,
+                    };
+                    barrier.wait();
+                    cache.get_or_calculate(key, || {
+                        calls.fetch_add(1, Ordering::SeqCst);
+                        match pokeapi.get_description("bulbasaur")? {
+                            Lookup::Found(d) => Ok(CacheEntry::new(Some(d))),
+                            _ => Err(anyhow!("Not found")),
+                        }
+                    })
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            let entry = thread.join().unwrap().unwrap();
+            assert_eq!(entry.description.as_deref(), Some("desc"));
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(cache.stats().misses, 1);
+    }
+
+    #[test]
+    fn test_cache_calculates_keys_in_parallel() {
+        let cache = Arc::new(Cache::new(4));
+        let key = |id| CacheKey {
+            species: Species::Id(id),
+            first_sentence: false,
+            style: Style::Shakespeare,
+            raw: false,
+        };
+        let (started, wait_started) = mpsc::channel();
+        let (finish, wait_finish) = mpsc::channel::<()>();
+        let slow = {
+            let cache = cache.clone();
+            thread::spawn(move || {
+                cache.get_or_calculate(key(1), || {
+                    started.send(()).unwrap();
+                    wait_finish.recv().unwrap();
+                    Ok::<_, ()>(CacheEntry::new(Some("slow".into())))
+                })
+            })
+        };
+
+        // Another key is calculated while the first one is still in progress
+        wait_started.recv().unwrap();
+        let fast = cache
+            .get_or_calculate(key(2), || Ok::<_, ()>(CacheEntry::new(Some("fast".into()))))
+            .unwrap();
+        assert_eq!(fast.description.as_deref(), Some("fast"));
+        finish.send(()).unwrap();
+        let slow = slow.join().unwrap().unwrap();
+        assert_eq!(slow.description.as_deref(), Some("slow"));
+    }
+
+    #[test]
+    fn test_cache_stats() {
+        let cache = Cache::new(4);
+        let key = CacheKey {
+            species: Species::Id(1),
+            first_sentence: false,
+            style: Style::Shakespeare,
+            raw: false,
+        };
+        let calculate = || Ok::<_, ()>(CacheEntry::new(Some("desc".into())));
+        let stats = |len, hits, misses| CacheStats {
+            capacity: 4,
+            len,
+            hits,
+            misses,
+        };
+
+        assert_eq!(cache.stats(), stats(0, 0, 0));
+        cache.get_or_calculate(key.clone(), calculate).unwrap();
+        assert_eq!(cache.stats(), stats(1, 0, 1));
+        cache.get_or_calculate(key, calculate).unwrap();
+        assert_eq!(cache.stats(), stats(1, 1, 1));
+    }
+
+    #[test]
+    fn test_canonical_names_memoized() {
+        let names = CanonicalNames::new(4);
+        let calls = Cell::new(0);
+        let canonicalize = |raw: &Alpha| {
+            calls.set(calls.get() + 1);
+            raw.canonical()
+        };
+        let raw = Alpha::try_new("Pikachu".into()).unwrap();
+
+        for _ in 0..3 {
+            assert_eq!(&*names.get_or_canonicalize(&raw, canonicalize), "pikachu");
+        }
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn test_pokeapi_path_template() {
+        let server = MockServer::start(|_| {
+            (
+                200,
+                r#"{"flavor_text_entries":[{"flavor_text":"desc","language":{"name":"en"}}]}"#
+                    .into(),
+            )
+        });
+        let client = PokeApiClient {
+            url: format!("{}/api/", server.url),
+            path_template: "species/{name}/".into(),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            client.get_description("farfetch'd").unwrap(),
+            Lookup::Found("desc".into())
+        );
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].path, "/api/species/farfetch%27d/");
+        assert_eq!(
+            requests[0].header("User-Agent"),
+            Some(concat!("poke_shakespeare/", env!("CARGO_PKG_VERSION")))
+        );
+    }
+
+    #[test]
+    fn test_pokeapi_list_names() {
+        let server = MockServer::start(|_| {
+            (
+                200,
+                r#"{"results":[{"name":"bulbasaur"},{"name":"ivysaur"}]}"#.into(),
+            )
+        });
+        let client = PokeApiClient {
+            url: format!("{}/api/", server.url),
+            path_template: "species/{name}/".into(),
+            ..Default::default()
+        };
+
+        assert_eq!(client.list_names().unwrap(), vec!["bulbasaur", "ivysaur"]);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/api/species/?limit=100000");
+    }
+
+    #[test]
+    fn test_species_names_retry_delay() {
+        struct FlakyList {
+            calls: Cell<usize>,
+            down: Cell<bool>,
+        }
+
+        impl PokeApi for FlakyList {
+            fn get_description(&self, _name: &str) -> Result<Lookup> {
+                Ok(Lookup::Missing)
+            }
+
+            fn list_names(&self) -> Result<Vec<String>> {
+                self.calls.set(self.calls.get() + 1);
+                if self.down.get() {
+                    return Err(anyhow!("PokeAPI is down"));
+                }
+                Ok(vec!["pikachu".into()])
+            }
+        }
+
+        let pokeapi = FlakyList {
+            calls: Cell::new(0),
+            down: Cell::new(true),
+        };
+        let names = SpeciesNames::default();
+        assert!(names.get(&pokeapi).is_none());
+        assert!(names.get(&pokeapi).is_none());
+        assert_eq!(pokeapi.calls.get(), 1);
+
+        let names = SpeciesNames::new(Duration::from_millis(0));
+        assert!(names.get(&pokeapi).is_none());
+        pokeapi.down.set(false);
+        assert_eq!(*names.get(&pokeapi).unwrap(), vec!["pikachu"]);
+        assert_eq!(*names.get(&pokeapi).unwrap(), vec!["pikachu"]);
+        assert_eq!(pokeapi.calls.get(), 3);
+    }
+
+    #[test]
+    fn test_translation_ping() {
+        let server = MockServer::start(|_| (405, "{}".into()));
+        let api = FunTranslationsApi {
+            url: server.url.clone(),
+            ..Default::default()
+        };
+        api.ping().unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "HEAD");
+
+        let server = MockServer::start(|_| (503, "{}".into()));
+        let api = FunTranslationsApi {
+            url: server.url.clone(),
+            ..Default::default()
+        };
+        api.ping().unwrap_err();
+        // Pings aren't retried
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_translation_retries() {
+        let responses = Arc::new(AtomicUsize::new(0));
+        let server = MockServer::start({
+            let responses = responses.clone();
+            move |_| match responses.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => (429, r#"{"error":{"code":429}}"#.into()),
+                _ => (200, r#"{"contents":{"translated":"Thee"}}"#.into()),
+            }
+        });
+        let mut api = FunTranslationsApi {
+            url: server.url.clone(),
+            retry_base_delay: Duration::from_millis(10),
+            ..Default::default()
+        };
+
+        assert_eq!(api.translate("You").unwrap(), "Thee");
+        assert_eq!(server.requests().len(), 3);
+
+        responses.store(0, Ordering::SeqCst);
+        api.max_attempts = 2;
+        let e = api.translate("You").unwrap_err();
+        assert!(unavailable_cause(&e).is_some());
+        assert_eq!(server.requests().len(), 5);
+    }
+
+    #[test]
+    fn test_translation_retry_delay_cap() {
+        let server = MockServer::start_with_headers(|_| {
+            (
+                429,
+                vec![("Retry-After".into(), "3600".into())],
+                r#"{"error":{"code":429}}"#.into(),
+            )
+        });
+        let api = FunTranslationsApi {
+            url: server.url.clone(),
+            ..Default::default()
+        };
+
+        // Waiting longer than the max delay fails right away with the requested delay
+        let e = api.translate("You").unwrap_err();
+        let unavailable = unavailable_cause(&e).unwrap();
+        assert_eq!(unavailable.retry_after(), Duration::from_secs(3600));
+        assert_eq!(server.requests().len(), 1);
+
+        // Backoff delays are capped instead of overflowing
+        let server = MockServer::start(|_| (503, "{}".into()));
+        let api = FunTranslationsApi {
+            url: server.url.clone(),
+            max_attempts: 40,
+            retry_base_delay: Duration::from_secs(u64::MAX / 4),
+            max_retry_delay: Duration::from_millis(1),
+            ..Default::default()
+        };
+        let e = api.translate("You").unwrap_err();
+        assert_eq!(
+            unavailable_cause(&e).unwrap().retry_after(),
+            Duration::from_millis(1)
+        );
+        assert_eq!(server.requests().len(), 40);
+    }
+
+    #[test]
+    fn test_flavor_text_strategy() {
+        let server = MockServer::start(|_| {
+            (
+                200,
+                r#"{"flavor_text_entries":[
+                    {"flavor_text":"Short.","language":{"name":"en"},"version":{"name":"red"}},
+                    {"flavor_text":"Lang.","language":{"name":"de"},"version":{"name":"x"}},
+                    {"flavor_text":"A longer one.","language":{"name":"en"},"version":{"name":"gold"}},
+                    {"flavor_text":"Latest.","language":{"name":"en"},"version":{"name":"sword"}},
+                    {"flavor_text":"Sprache, länger als alle.","language":{"name":"de"}}
+                ]}"#
+                .into(),
+            )
+        });
+        let description = |strategy| {
+            PokeApiClient {
+                url: format!("{}/", server.url),
+                flavor_text_strategy: strategy,
+                ..Default::default()
+            }
+            .get_description("pikachu")
+            .unwrap()
+        };
+
+        let found = |text: &str| Lookup::Found(text.into());
+        assert_eq!(description(FlavorTextStrategy::First), found("Short."));
+        assert_eq!(description(FlavorTextStrategy::Last), found("Latest."));
+        assert_eq!(
+            description(FlavorTextStrategy::Longest),
+            found("A longer one.")
+        );
+        for _ in 0..10 {
+            let random = description(FlavorTextStrategy::Random);
+            assert!(["Short.", "A longer one.", "Latest."]
+                .iter()
+                .any(|&text| random == found(text)));
+        }
+    }
+
+    #[test]
+    fn test_flavor_text_language() {
+        let server = MockServer::start(|request| {
+            let body = match request.path.as_str() {
+                "/pikachu" => {
+                    r#"{"flavor_text_entries":[
+                        {"flavor_text":"ねずみ","language":{"name":"ja"}},
+                        {"flavor_text":"Souris.","language":{"name":"fr"}},
+                        {"flavor_text":"Mouse.","language":{"name":"en"}}
+                    ]}"#
+                }
+                "/eevee" => {
+                    r#"{"flavor_text_entries":[{"flavor_text":"Fox.","language":{"name":"en"}}]}"#
+                }
+                _ => {
+                    r#"{"flavor_text_entries":[{"flavor_text":"Chose.","language":{"name":"fr"}}]}"#
+                }
+            };
+            (200, body.into())
+        });
+        let client = |language: &str| PokeApiClient {
+            url: format!("{}/", server.url),
+            language: language.into(),
+            ..Default::default()
+        };
+
+        let description = |language, name| client(language).get_description(name).unwrap();
+        let found = |text: &str| Lookup::Found(text.into());
+        assert_eq!(description("en", "pikachu"), found("Mouse."));
+        assert_eq!(description("fr", "pikachu"), found("Souris."));
+        assert_eq!(description("ja", "pikachu"), found("ねずみ"));
+        assert_eq!(description("fr", "eevee"), found("Fox."));
+        assert_eq!(description("ja", "missingno"), Lookup::NoDescription);
+    }
+
+    #[test]
+    fn test_translation_styles() {
+        let server = MockServer::start(|request| {
+            (
+                200,
+                format!(r#"{{"contents":{{"translated":"{}"}}}}"#, request.path),
+            )
+        });
+        let api = FunTranslationsApi {
+            url: format!("{}/translate/shakespeare", server.url),
+            ..Default::default()
+        };
+
+        assert_eq!(api.translate("Hi").unwrap(), "/translate/shakespeare");
+        let (translation, _) = api.translate_styled("Hi", Style::Yoda).unwrap();
+        assert_eq!(translation, "/translate/yoda");
+        assert_eq!("pirate".parse::<Style>().unwrap(), Style::Pirate);
+        "klingon".parse::<Style>().unwrap_err();
+    }
+
+    #[test]
+    fn test_pokeapi_reuses_connections() {
+        let server = MockServer::start(|_| (200, r#"{"flavor_text_entries":[]}"#.into()));
+        let client = PokeApiClient {
+            url: format!("{}/", server.url),
+            ..Default::default()
+        };
+
+        client.get_description("pikachu").unwrap();
+        client.get_description("bulbasaur").unwrap();
+        assert_eq!(server.requests().len(), 2);
+        assert_eq!(server.connections(), 1);
     }
 }
@@ -0,0 +1,149 @@
+//! Helpers for tests exercising the HTTP clients in `services`.
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Request received by `MockServer`.
+#[derive(Clone, Debug)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl MockRequest {
+    /// Returns the value of the first header named `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Minimal HTTP/1.1 server listening on localhost. Every request is recorded and answered with the
+/// status, extra headers and JSON body returned by the given closure.
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+    connections: Arc<AtomicUsize>,
+}
+
+impl MockServer {
+    /// Starts a server answering without extra headers.
+    pub fn start<F>(respond: F) -> Self
+    where
+        F: Fn(&MockRequest) -> (u16, String) + Send + Sync + 'static,
+    {
+        MockServer::start_with_headers(move |request| {
+            let (status, body) = respond(request);
+            (status, Vec::new(), body)
+        })
+    }
+
+    pub fn start_with_headers<F>(respond: F) -> Self
+    where
+        F: Fn(&MockRequest) -> (u16, Vec<(String, String)>, String) + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let connections = Arc::new(AtomicUsize::new(0));
+        let respond = Arc::new(respond);
+        {
+            let requests = requests.clone();
+            let connections = connections.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    connections.fetch_add(1, Ordering::SeqCst);
+                    let requests = requests.clone();
+                    let respond = respond.clone();
+                    thread::spawn(move || serve(stream.unwrap(), &*respond, &requests));
+                }
+            });
+        }
+        MockServer {
+            url,
+            requests,
+            connections,
+        }
+    }
+
+    /// Number of TCP connections accepted so far.
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    /// Requests received so far.
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn serve<F>(stream: TcpStream, respond: &F, requests: &Mutex<Vec<MockRequest>>)
+where
+    F: Fn(&MockRequest) -> (u16, Vec<(String, String)>, String),
+{
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+    while let Some(request) = read_request(&mut reader) {
+        requests.lock().unwrap().push(request.clone());
+        let (status, headers, body) = respond(&request);
+        let headers: String = headers
+            .iter()
+            .map(|(k, v)| format!("{}: {}\r\n", k, v))
+            .collect();
+        let response = format!(
+            "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}\r\n{}",
+            status,
+            body.len(),
+            headers,
+            body
+        );
+        if writer.write_all(response.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+fn read_request<R: BufRead>(reader: &mut R) -> Option<MockRequest> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).ok()? == 0 {
+        return None;
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        let mut kv = line.splitn(2, ':');
+        let k = kv.next()?.trim().to_string();
+        let v = kv.next().unwrap_or("").trim().to_string();
+        headers.push((k, v));
+    }
+
+    let mut request = MockRequest {
+        method,
+        path,
+        headers,
+        body: String::new(),
+    };
+    let len = request
+        .header("content-length")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body).ok()?;
+    request.body = String::from_utf8_lossy(&body).into_owned();
+    Some(request)
+}
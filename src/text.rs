@@ -0,0 +1,300 @@
+//! Helpers to process description text.
+
+/// Words which are commonly followed by a period without ending a sentence.
+const ABBREVIATIONS: &[&str] = &[
+    "mr", "mrs", "ms", "dr", "st", "mt", "vs", "etc", "e.g", "i.e",
+];
+
+/// Words which mark text as already sounding Shakespearean.
+const ARCHAIC_WORDS: &[&str] = &["thee", "thou", "thy", "thine", "hath", "doth"];
+
+/// Returns whether `text` contains archaic words such as "thee" or "hath".
+pub fn is_archaic(text: &str) -> bool {
+    text.split(|c: char| !c.is_alphabetic())
+        .any(|word| ARCHAIC_WORDS.contains(&word.to_lowercase().as_str()))
+}
+
+/// Straight and typographic apostrophes, as typed in names such as "Farfetch'd".
+const APOSTROPHES: &[char] = &['\'', '\u{2018}', '\u{2019}', '\u{02BC}'];
+
+/// Removes all apostrophes from `text`.
+pub fn strip_apostrophes(text: &str) -> String {
+    text.chars().filter(|c| !APOSTROPHES.contains(c)).collect()
+}
+
+/// Maps form descriptions such as "Mega Charizard X" or "Gigantamax Pikachu" to PokeAPI slugs
+/// like `charizard-mega-x` and `pikachu-gmax`. Returns `None` if `name` doesn't describe a form.
+pub fn form_slug(name: &str) -> Option<String> {
+    let words: Vec<_> = name.split_whitespace().map(str::to_lowercase).collect();
+    match words.split_first()? {
+        (form, [species, variant @ ..]) if form == "mega" => {
+            let mut slug = format!("{}-mega", species);
+            for v in variant {
+                slug.push('-');
+                slug.push_str(v);
+            }
+            Some(slug)
+        }
+        (form, species) if (form == "gigantamax" || form == "gmax") && !species.is_empty() => {
+            Some(format!("{}-gmax", species.join("-")))
+        }
+        _ => None,
+    }
+}
+
+/// Words left lowercase by `title_case` unless they start or end the text: articles, coordinating
+/// conjunctions and short prepositions.
+const MINOR_WORDS: &[&str] = &[
+    "a", "an", "the", "and", "but", "or", "nor", "for", "so", "yet", "as", "at", "by", "in", "of",
+    "off", "on", "per", "to", "up", "via",
+];
+
+/// Converts `text` to English title case: words are capitalized, except for minor words such as
+/// "the" or "of" which are lowercased unless they're the first or last word. Whitespace is kept
+/// as is.
+pub fn title_case(text: &str) -> String {
+    let last = text.split_whitespace().count().saturating_sub(1);
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    let mut index = 0;
+    loop {
+        let trimmed = rest.trim_start();
+        result.push_str(&rest[..rest.len() - trimmed.len()]);
+        rest = trimmed;
+        if rest.is_empty() {
+            return result;
+        }
+        let end = rest.find(char::is_whitespace).unwrap_or_else(|| rest.len());
+        let word = &rest[..end];
+        rest = &rest[end..];
+
+        let bare: String = word.chars().filter(|c| c.is_alphabetic()).collect();
+        let minor = MINOR_WORDS.contains(&bare.to_lowercase().as_str());
+        if minor && index != 0 && index != last {
+            result.push_str(&word.to_lowercase());
+        } else {
+            let mut chars = word.chars();
+            for c in &mut chars {
+                if c.is_alphabetic() {
+                    result.extend(c.to_uppercase());
+                    break;
+                }
+                result.push(c);
+            }
+            result.push_str(chars.as_str());
+        }
+        index += 1;
+    }
+}
+
+/// Number of single character insertions, deletions and substitutions turning `a` into `b`.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    // Distances from the prefix of `a` read so far to each prefix of `b`
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Returns up to `max` of `candidates` within an edit distance of half the length of `name`,
+/// closest first. `name` itself is never suggested.
+pub fn suggestions<'a, I>(name: &str, candidates: I, max: usize) -> Vec<String>
+where
+    I: IntoIterator<Item = &'a String>,
+{
+    let threshold = (name.chars().count() / 2).max(1);
+    let mut close: Vec<_> = candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance > 0 && distance <= threshold)
+        .collect();
+    close.sort();
+    close
+        .into_iter()
+        .take(max)
+        .map(|(_, candidate)| candidate.clone())
+        .collect()
+}
+
+/// Collapses runs of whitespace, including newlines and form feeds, into single spaces and trims
+/// the ends.
+pub fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Capitalizes the first letter of `text` and appends a period unless it already ends in `.`, `!`
+/// or `?`. Trailing whitespace is trimmed, empty text is left as is.
+pub fn punctuate_sentence(text: &str) -> String {
+    let text = text.trim_end();
+    let mut chars = text.chars();
+    let mut sentence: String = match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => return String::new(),
+    };
+    if !sentence.ends_with(|c| c == '.' || c == '!' || c == '?') {
+        sentence.push('.');
+    }
+    sentence
+}
+
+/// Estimates how long it takes to read `text` in whole seconds, rounded up, given a reading speed
+/// in words per minute.
+pub fn reading_time_secs(text: &str, words_per_minute: u64) -> u64 {
+    let words = text.split_whitespace().count() as u64;
+    (words * 60 + words_per_minute - 1) / words_per_minute
+}
+
+/// Returns the first sentence of `text`, or all of it if it's a single sentence. Sentences end in
+/// `.`, `!` or `?` followed by whitespace, except for periods after abbreviations or initials.
+pub fn first_sentence(text: &str) -> &str {
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let at_boundary = chars
+            .peek()
+            .map_or(false, |&(_, next)| next.is_whitespace());
+        if !at_boundary || !(c == '.' || c == '!' || c == '?') {
+            continue;
+        }
+        if c == '.' {
+            let word = text[..i].rsplit(char::is_whitespace).next().unwrap_or("");
+            // Single letters and dotted words such as "A.B." are initials
+            let is_initial = word.chars().count() == 1 || word.contains('.');
+            if is_initial || ABBREVIATIONS.contains(&word.to_lowercase().as_str()) {
+                continue;
+            }
+        }
+        return &text[..=i];
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_archaic() {
+        assert!(is_archaic("Thou shalt not pass."));
+        assert!(is_archaic("It hath a tail"));
+        assert!(!is_archaic("A strange seed was planted on its back."));
+        assert!(!is_archaic("Thousands gather at the pathway"));
+    }
+
+    #[test]
+    fn test_strip_apostrophes() {
+        assert_eq!(strip_apostrophes("Farfetch'd"), "Farfetchd");
+        assert_eq!(strip_apostrophes("Farfetch\u{2019}d"), "Farfetchd");
+        assert_eq!(strip_apostrophes("farfetchd"), "farfetchd");
+    }
+
+    #[test]
+    fn test_form_slug() {
+        assert_eq!(
+            form_slug("Mega Charizard X").as_deref(),
+            Some("charizard-mega-x")
+        );
+        assert_eq!(form_slug("mega venusaur").as_deref(), Some("venusaur-mega"));
+        assert_eq!(
+            form_slug("Gigantamax Pikachu").as_deref(),
+            Some("pikachu-gmax")
+        );
+        assert_eq!(form_slug("Pikachu"), None);
+        assert_eq!(form_slug("Mega"), None);
+    }
+
+    #[test]
+    fn test_title_case() {
+        assert_eq!(title_case("the lord of the rings"), "The Lord of the Rings");
+        assert_eq!(
+            title_case("A mouse With a Tail  for (the) taking"),
+            "A Mouse With a Tail  for (the) Taking"
+        );
+        assert_eq!(title_case("what is it for"), "What Is It For");
+        assert_eq!(title_case("'tis thy pokémon"), "'Tis Thy Pokémon");
+        assert_eq!(title_case(""), "");
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("charizard", "charizard"), 0);
+        assert_eq!(edit_distance("charzard", "charizard"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "mew"), 3);
+        assert_eq!(edit_distance("pokémon", "pokemon"), 1);
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let names: Vec<String> = vec!["pikachu", "charmander", "charizard", "charmeleon", "mew"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            suggestions("charzard", &names, 5),
+            vec!["charizard", "charmander"]
+        );
+        assert_eq!(suggestions("charzard", &names, 1), vec!["charizard"]);
+        assert_eq!(suggestions("mew", &names, 5), Vec::<String>::new());
+        assert_eq!(suggestions("xyz", &names, 5), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_collapse_whitespace() {
+        assert_eq!(
+            collapse_whitespace(" Thou  art\na\u{c}pokémon \n"),
+            "Thou art a pokémon"
+        );
+        assert_eq!(collapse_whitespace(""), "");
+    }
+
+    #[test]
+    fn test_punctuate_sentence() {
+        assert_eq!(
+            punctuate_sentence("electricity, it stores"),
+            "Electricity, it stores."
+        );
+        assert_eq!(punctuate_sentence("Strong, it is!"), "Strong, it is!");
+        assert_eq!(punctuate_sentence("électrique, hmm \n"), "Électrique, hmm.");
+        assert_eq!(punctuate_sentence(" "), "");
+    }
+
+    #[test]
+    fn test_reading_time_secs() {
+        assert_eq!(reading_time_secs("", 200), 0);
+        assert_eq!(reading_time_secs("one", 200), 1);
+        assert_eq!(reading_time_secs(&"word ".repeat(200), 200), 60);
+        assert_eq!(reading_time_secs(&"word ".repeat(201), 200), 61);
+    }
+
+    #[test]
+    fn test_first_sentence() {
+        assert_eq!(first_sentence("One. Two."), "One.");
+        assert_eq!(first_sentence("One!\nTwo?"), "One!");
+        assert_eq!(first_sentence("Just one."), "Just one.");
+        assert_eq!(first_sentence("No period"), "No period");
+        assert_eq!(
+            first_sentence("Mr. Mime waves. It's a mime."),
+            "Mr. Mime waves."
+        );
+        assert_eq!(
+            first_sentence("Found in Mt. Moon. Rare."),
+            "Found in Mt. Moon."
+        );
+        assert_eq!(
+            first_sentence("A.B. Smith saw it. Then left."),
+            "A.B. Smith saw it."
+        );
+        assert_eq!(
+            first_sentence("Version 2.5 is out. Yes."),
+            "Version 2.5 is out."
+        );
+    }
+}